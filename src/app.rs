@@ -6,20 +6,23 @@ use winit::{
     event_loop::*,
     window::*
 };
-use winit::event::ElementState;
-use winit::keyboard::{KeyCode, PhysicalKey};
+use crate::engine::controllers::fly::*;
+use crate::engine::controllers::orbit::*;
 use crate::engine::engine::*;
+use crate::engine::input::bindings::*;
 
+/// Файл с раскладкой управления
+const CONTROLS_PATH: &str = "config/controls.cfg";
+/// Шаблон фонаря для демонстрационной сцены
+const LAMP_POST_PREFAB_PATH: &str = "assets/prefabs/lamp_post.prefab";
+/// Снимок мира для быстрого сохранения и загрузки
+const QUICK_SAVE_PATH: &str = "saves/quick.snapshot";
+
+#[derive(Default)]
 pub struct App<'a> {
     engine: Option<Engine<'a>>
 }
 
-impl<'a> Default for App<'a> {
-    fn default() -> Self {
-        Self { engine: None }
-    }
-}
-
 impl<'a> ApplicationHandler for App<'a> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window = event_loop.create_window(
@@ -30,71 +33,101 @@ impl<'a> ApplicationHandler for App<'a> {
         ).unwrap();
 
         let window = Arc::new(window);
-        let engine = Engine::new(window.clone());
+        let mut engine = Engine::new(window.clone());
 
-        self.engine = Some(engine);
+        engine.input_mut().map = InputMap::load_or_create(CONTROLS_PATH);
+        for conflict in engine.input().map.conflicts() {
+            eprintln!("Кнопка {:?} назначена нескольким действиям: {}", conflict.binding, conflict.actions.join(", "));
+        }
+
+        engine.set_camera_controller(FlyCameraController::new());
+
+        if let Err(e) = engine.load_prefab(LAMP_POST_PREFAB_PATH) {
+            eprintln!("Ошибка загрузки шаблона: {}", e);
+        }
+
+        crate::scenes::register(&mut engine);
+        engine.load_scene(crate::scenes::START_SCENE);
 
-        crate::scenes::_1::load(self.engine.as_mut().unwrap()); // TODO: Добавить менеджер сцен
+        self.engine = Some(engine);
 
         window.request_redraw();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
-        if let Some(engine) = &mut self.engine {
-            if Arc::ptr_eq(&engine.window, &engine.window) && engine.window.id() == id {
-                match event {
-                    WindowEvent::CloseRequested => event_loop.exit(),
-                    WindowEvent::Resized(size) => {
-                        engine.renderer.resize(size);
-                        engine.window.request_redraw();
-                    }
-                    WindowEvent::ScaleFactorChanged { .. } => {
-                        engine.window.request_redraw();
-                    }
-                    WindowEvent::RedrawRequested => {
-                        if let Some(engine) = &mut self.engine {
-                            if let Err(e) = engine.render() {
-                                eprintln!("Ошибка отрисовки: {:?}", e);
-                            }
-
-                            engine.window.request_redraw();
-                        }
-                    }
-                    WindowEvent::KeyboardInput { event, .. } => { // TODO: Сделать управление отдельно
-                        let keycode = event.physical_key;
-    
-                        if event.state == ElementState::Pressed {
-                            let camera = engine.get_camera_mut();
-                            
-                            match keycode {
-                                PhysicalKey::Code(KeyCode::KeyW) => { camera.move_forward(0.5); }
-                                PhysicalKey::Code(KeyCode::KeyS) => { camera.move_forward(-0.5); }
-                                PhysicalKey::Code(KeyCode::KeyD) => { camera.move_right(0.5); }
-                                PhysicalKey::Code(KeyCode::KeyA) => { camera.move_right(-0.5); }
-    
-                                PhysicalKey::Code(KeyCode::ArrowUp) => { camera.rotate_pitch(0.2) }
-                                PhysicalKey::Code(KeyCode::ArrowDown) => { camera.rotate_pitch(-0.2) }
-                                PhysicalKey::Code(KeyCode::ArrowLeft) => { camera.rotate_yaw(0.2) }
-                                PhysicalKey::Code(KeyCode::ArrowRight) => { camera.rotate_yaw(-0.2) }
-                                PhysicalKey::Code(KeyCode::KeyE) => { camera.rotate_roll(0.2) }
-                                PhysicalKey::Code(KeyCode::KeyQ) => { camera.rotate_roll(-0.2) }
-    
-                                PhysicalKey::Code(KeyCode::Space) => { camera.move_up(0.5) }
-                                PhysicalKey::Code(KeyCode::ShiftLeft) => { camera.move_up(-0.5) }
-    
-                                PhysicalKey::Code(KeyCode::Equal) => { camera.fov += 1.0; }
-                                PhysicalKey::Code(KeyCode::Minus) => { camera.fov -= 1.0; }
-                                _ => {}
-                            }
-                        }
+        if let Some(engine) = &mut self.engine && engine.window.id() == id {
+            engine.handle_window_event(&event);
+
+            match event {
+                WindowEvent::CloseRequested => event_loop.exit(),
+                WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                    engine.window.request_redraw();
+                }
+                WindowEvent::RedrawRequested => {
+                    self.handle_controls();
+
+                    let engine = self.engine.as_mut().unwrap();
+                    engine.update();
+
+                    if let Err(e) = engine.render() {
+                        eprintln!("Ошибка отрисовки: {:?}", e);
                     }
-                    _ => {}
+
+                    engine.window.request_redraw();
                 }
+                _ => {}
             }
         }
     }
 
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        if let Some(engine) = &mut self.engine {
+            engine.input_mut().handle_device_event(&event);
+        }
+    }
+
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
         self.engine = None;
     }
 }
+
+impl<'a> App<'a> {
+    fn handle_controls(&mut self) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+
+        if engine.input().action_pressed("pause") {
+            engine.time_mut().toggle_pause();
+        }
+
+        // Режим берётся из установленного контроллера: сцены ставят свои
+        if engine.input().action_pressed("switch_camera") {
+            if engine.has_camera_controller::<OrbitCameraController>() {
+                engine.set_camera_controller(FlyCameraController::new());
+            } else {
+                let orbit = OrbitCameraController::from_camera(engine.get_camera_mut(), 5.0);
+                engine.set_camera_controller(orbit);
+            }
+        }
+
+        if engine.input().action_pressed("scene_1") { engine.switch_scene("_1"); }
+        if engine.input().action_pressed("scene_2") { engine.switch_scene("_2"); }
+        if engine.input().action_pressed("scene_3") { engine.switch_scene("demo"); }
+
+        if engine.input().action_pressed("save_scene")
+            && let Err(e) = engine.save_scene_file("assets/scenes/saved.scene") {
+            eprintln!("Ошибка сохранения сцены: {}", e);
+        }
+
+        if engine.input().action_pressed("quick_save")
+            && let Err(e) = engine.save_snapshot(QUICK_SAVE_PATH) {
+            eprintln!("Ошибка быстрого сохранения: {}", e);
+        }
+
+        if engine.input().action_pressed("quick_load")
+            && let Err(e) = engine.load_snapshot(QUICK_SAVE_PATH) {
+            eprintln!("Ошибка быстрой загрузки: {}", e);
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use crate::engine::ecs::*;
+
+/// Такты добавления и последнего изменения компонента
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32
+}
+
+impl ComponentTicks {
+    pub fn is_added(&self, since: u32) -> bool {
+        self.added > since
+    }
+
+    pub fn is_changed(&self, since: u32) -> bool {
+        self.changed > since
+    }
+}
+
+/// Хранилище компонентов одного типа. Запоминает, когда компонент был добавлен
+/// и изменён, и какие сущности его потеряли. Изменением считается каждая выданная
+/// изменяемая ссылка, а не сам вызов: частичный обход отмечает только пройденные компоненты
+pub struct Storage<T> {
+    data: HashMap<Entity, T>,
+    ticks: HashMap<Entity, ComponentTicks>,
+    removed: Vec<(Entity, u32)>,
+    tick: u32
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self {
+            data: HashMap::new(),
+            ticks: HashMap::new(),
+            removed: Vec::new(),
+            tick: 0
+        }
+    }
+}
+
+impl<T: 'static> Storage<T> {
+    /// Замена существующего компонента считается изменением, а не добавлением
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        let tick = self.tick;
+        self.ticks
+            .entry(entity)
+            .and_modify(|t| t.changed = tick)
+            .or_insert(ComponentTicks { added: tick, changed: tick });
+
+        self.data.insert(entity, component)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.data.get(&entity)
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let component = self.data.get_mut(&entity)?;
+        if let Some(ticks) = self.ticks.get_mut(&entity) {
+            ticks.changed = self.tick;
+        }
+
+        Some(component)
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let component = self.data.remove(&entity)?;
+        self.ticks.remove(&entity);
+        self.removed.push((entity, self.tick));
+
+        Some(component)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.data.contains_key(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.data.iter().map(|(e, c)| (*e, c))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        let tick = self.tick;
+        let ticks = &mut self.ticks;
+
+        self.data.iter_mut().map(move |(e, c)| {
+            if let Some(ticks) = ticks.get_mut(e) {
+                ticks.changed = tick;
+            }
+
+            (*e, c)
+        })
+    }
+
+    /// Change detection
+    pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        self.ticks.get(&entity).copied()
+    }
+
+    pub fn added(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.ticks.iter().filter(move |(_, t)| t.is_added(since)).map(|(e, _)| *e)
+    }
+
+    /// Включает добавленные
+    pub fn changed(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.ticks.iter().filter(move |(_, t)| t.is_changed(since)).map(|(e, _)| *e)
+    }
+
+    pub fn removed(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.removed.iter().filter(move |(_, t)| *t > since).map(|(e, _)| *e)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.data.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Хранилище с неизвестным типом, для удаления сущностей из всех хранилищ сразу
+trait AnyStorage {
+    fn remove_entity(&mut self, entity: Entity);
+    fn clear(&mut self);
+    fn set_tick(&mut self, tick: u32);
+    fn forget_removed(&mut self, before: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyStorage for Storage<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn clear(&mut self) {
+        let tick = self.tick;
+        self.removed.extend(self.data.keys().map(|e| (*e, tick)));
+        self.data.clear();
+        self.ticks.clear();
+    }
+
+    fn set_tick(&mut self, tick: u32) {
+        self.tick = tick;
+    }
+
+    fn forget_removed(&mut self, before: u32) {
+        self.removed.retain(|(_, t)| *t >= before);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Компоненты всех типов. Компонентом может быть любой `'static` тип,
+/// хранилище создаётся при первой вставке.
+///
+/// Изменения отмечаются текущим тактом. Такт растёт при каждом вызове
+/// `change_tick`, поэтому всё изменённое после вызова будет новее возвращённого значения
+pub struct Components {
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    tick: u32
+}
+
+impl Default for Components {
+    fn default() -> Self {
+        Self {
+            storages: HashMap::new(),
+            tick: 1
+        }
+    }
+}
+
+impl Components {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: 'static>(&mut self) {
+        let tick = self.tick;
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Storage::<T> { tick, ..Default::default() }));
+    }
+
+    /// Такт, с которым сравнивать при следующей проверке изменений
+    pub fn change_tick(&mut self) -> u32 {
+        let tick = self.tick;
+        self.tick += 1;
+
+        for storage in self.storages.values_mut() {
+            storage.set_tick(self.tick);
+        }
+
+        tick
+    }
+
+    /// Забыть удаления старше такта
+    pub fn forget_removed(&mut self, before: u32) {
+        for storage in self.storages.values_mut() {
+            storage.forget_removed(before);
+        }
+    }
+
+    pub fn added<T: 'static>(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.storage::<T>().into_iter().flat_map(move |s| s.added(since))
+    }
+
+    pub fn changed<T: 'static>(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.storage::<T>().into_iter().flat_map(move |s| s.changed(since))
+    }
+
+    pub fn removed<T: 'static>(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.storage::<T>().into_iter().flat_map(move |s| s.removed(since))
+    }
+
+    pub fn is_changed<T: 'static>(&self, entity: Entity, since: u32) -> bool {
+        self.storage::<T>()
+            .and_then(|s| s.ticks(entity))
+            .is_some_and(|t| t.is_changed(since))
+    }
+
+    pub fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|s| s.as_any().downcast_ref())
+    }
+
+    pub fn storage_mut<T: 'static>(&mut self) -> &mut Storage<T> {
+        self.register::<T>();
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|s| s.as_any_mut().downcast_mut())
+            .expect("Тип хранилища не совпадает с типом компонента")
+    }
+
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        self.storage_mut::<T>().insert(entity, component)
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>().and_then(|s| s.get(entity))
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|s| s.as_any_mut().downcast_mut::<Storage<T>>())
+            .and_then(|s| s.get_mut(entity))
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|s| s.as_any_mut().downcast_mut::<Storage<T>>())
+            .and_then(|s| s.remove(entity))
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.storage::<T>().is_some_and(|s| s.contains(entity))
+    }
+
+    pub fn remove_entity(&mut self, entity: Entity) {
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for storage in self.storages.values_mut() {
+            storage.clear();
+        }
+    }
+
+    /// Запросы
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>().into_iter().flat_map(|s| s.iter())
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|s| s.as_any_mut().downcast_mut::<Storage<T>>())
+            .into_iter()
+            .flat_map(|s| s.iter_mut())
+    }
+
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        let b = self.storage::<B>();
+
+        self.query::<A>().filter_map(move |(e, a)| Some((e, a, b?.get(e)?)))
+    }
+
+    pub fn query3<A: 'static, B: 'static, C: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B, &C)> {
+        let b = self.storage::<B>();
+        let c = self.storage::<C>();
+
+        self.query::<A>().filter_map(move |(e, a)| Some((e, a, b?.get(e)?, c?.get(e)?)))
+    }
+
+    /// Обход сущностей с изменяемым `A` и неизменяемым `B`.
+    /// Изменёнными отмечаются только `A` сущностей, у которых есть `B`
+    pub fn each2_mut<A: 'static, B: 'static>(&mut self, mut f: impl FnMut(Entity, &mut A, &B)) {
+        assert_ne!(TypeId::of::<A>(), TypeId::of::<B>(), "Один и тот же тип компонента");
+
+        let Some(mut a) = self.storages.remove(&TypeId::of::<A>()) else {
+            return;
+        };
+
+        if let (Some(a), Some(b)) = (a.as_any_mut().downcast_mut::<Storage<A>>(), self.storage::<B>()) {
+            for (e, component) in a.data.iter_mut() {
+                if let Some(b) = b.get(*e) {
+                    if let Some(ticks) = a.ticks.get_mut(e) {
+                        ticks.changed = a.tick;
+                    }
+
+                    f(*e, component, b);
+                }
+            }
+        }
+
+        self.storages.insert(TypeId::of::<A>(), a);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    fn sorted(entities: impl Iterator<Item = Entity>) -> Vec<Entity> {
+        let mut entities = entities.collect::<Vec<_>>();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn changes_are_newer_than_the_returned_tick() {
+        let mut entities = Entities::default();
+        let mut components = Components::new();
+        let (a, b) = (entities.create(), entities.create());
+
+        components.insert(a, Health(1));
+        let since = components.change_tick();
+
+        assert!(components.added::<Health>(since).next().is_none());
+        assert!(components.changed::<Health>(since).next().is_none());
+
+        components.insert(b, Health(2));
+        components.get_mut::<Health>(a).unwrap().0 = 5;
+
+        assert_eq!(sorted(components.added::<Health>(since)), [b]);
+        assert_eq!(sorted(components.changed::<Health>(since)), [a, b]);
+        assert!(components.is_changed::<Health>(a, since));
+
+        let since = components.change_tick();
+        components.get::<Health>(a);
+        assert!(!components.is_changed::<Health>(a, since));
+    }
+
+    #[test]
+    fn only_yielded_components_are_changed() {
+        let mut entities = Entities::default();
+        let mut components = Components::new();
+        let (a, b, c) = (entities.create(), entities.create(), entities.create());
+
+        components.insert(a, Health(1));
+        components.insert(b, Health(2));
+        components.insert(c, Health(3));
+        components.insert(b, 0.5f32);
+        let since = components.change_tick();
+
+        let (first, _) = components.query_mut::<Health>().next().unwrap();
+        assert_eq!(sorted(components.changed::<Health>(since)), [first]);
+
+        let since = components.change_tick();
+        components.each2_mut::<Health, f32>(|_, health, _| health.0 += 1);
+
+        assert_eq!(sorted(components.changed::<Health>(since)), [b]);
+        assert!(components.changed::<f32>(since).next().is_none());
+    }
+
+    #[test]
+    fn replacing_a_component_is_a_change() {
+        let mut entities = Entities::default();
+        let mut components = Components::new();
+        let entity = entities.create();
+
+        components.insert(entity, Health(1));
+        let since = components.change_tick();
+        assert_eq!(components.insert(entity, Health(2)), Some(Health(1)));
+
+        assert!(components.added::<Health>(since).next().is_none());
+        assert_eq!(sorted(components.changed::<Health>(since)), [entity]);
+    }
+
+    #[test]
+    fn removals_are_tracked_until_forgotten() {
+        let mut entities = Entities::default();
+        let mut components = Components::new();
+        let (a, b) = (entities.create(), entities.create());
+
+        components.insert(a, Health(1));
+        components.insert(b, Health(2));
+        let since = components.change_tick();
+
+        components.remove::<Health>(a);
+        components.remove_entity(b);
+
+        assert_eq!(sorted(components.removed::<Health>(since)), [a, b]);
+        assert!(components.changed::<Health>(since).next().is_none());
+        assert!(!components.has::<Health>(a));
+
+        let later = components.change_tick();
+        assert!(components.removed::<Health>(later).next().is_none());
+
+        components.forget_removed(later + 1);
+        assert!(components.removed::<Health>(since).next().is_none());
+    }
+
+    #[test]
+    fn clear_reports_every_component_as_removed() {
+        let mut entities = Entities::default();
+        let mut components = Components::new();
+        let (a, b) = (entities.create(), entities.create());
+
+        components.insert(a, Health(1));
+        components.insert(b, Health(2));
+        let since = components.change_tick();
+        components.clear();
+
+        assert_eq!(sorted(components.removed::<Health>(since)), [a, b]);
+        assert_eq!(components.query::<Health>().count(), 0);
+    }
+}
@@ -0,0 +1,30 @@
+use std::any::Any;
+use winit::window::CursorGrabMode;
+use crate::engine::engine::*;
+
+/// Управление камерой
+pub trait CameraController: Any {
+    fn update(&mut self, engine: &mut Engine, dt: f32);
+
+    /// Вызывается при замене контроллера другим
+    fn on_deactivate(&mut self, _engine: &mut Engine) {}
+}
+
+/// Захват курсора для обзора мышью
+pub fn grab_cursor(engine: &Engine, grab: bool) {
+    let window = &engine.window;
+
+    if grab {
+        let result = window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+
+        if let Err(e) = result {
+            eprintln!("Не удалось захватить курсор: {}", e);
+        }
+    } else {
+        let _ = window.set_cursor_grab(CursorGrabMode::None);
+    }
+
+    window.set_cursor_visible(!grab);
+}
@@ -0,0 +1,163 @@
+use std::f32::consts::FRAC_PI_2;
+use crate::engine::controllers::controller::*;
+use crate::engine::core::primitives::*;
+use crate::engine::engine::*;
+use crate::engine::render::camera::*;
+
+/// Свободный полёт: движение с инерцией, обзор мышью при захваченном курсоре
+pub struct FlyCameraController {
+    /// Максимальная скорость, ед/с
+    pub speed: f32,
+    /// Разгон до максимальной скорости, ед/с²
+    pub acceleration: f32,
+    /// Торможение без нажатых кнопок, ед/с²
+    pub deceleration: f32,
+    /// Поворот на пиксель движения мыши, рад
+    pub sensitivity: f32,
+    /// Поворот стрелками, рад/с
+    pub look_speed: f32,
+    /// Крен, рад/с
+    pub roll_speed: f32,
+    /// Запрет крена
+    pub roll_lock: bool,
+    /// Предел тангажа, рад
+    pub max_pitch: f32,
+    /// Изменение угла обзора, град/с
+    pub fov_speed: f32,
+    pub min_fov: f32,
+    pub max_fov: f32,
+    velocity: Vec3,
+    yaw: f32,
+    pitch: f32,
+    roll: f32,
+    rotation: Option<Quat>,
+    grabbed: bool
+}
+
+impl Default for FlyCameraController {
+    fn default() -> Self {
+        Self {
+            speed: 5.0,
+            acceleration: 20.0,
+            deceleration: 15.0,
+            sensitivity: 0.002,
+            look_speed: 1.5,
+            roll_speed: 1.5,
+            roll_lock: true,
+            max_pitch: FRAC_PI_2 - 0.01,
+            fov_speed: 30.0,
+            min_fov: 10.0,
+            max_fov: 120.0,
+            velocity: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            rotation: None,
+            grabbed: false
+        }
+    }
+}
+
+impl FlyCameraController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn with_roll_lock(mut self, roll_lock: bool) -> Self {
+        self.roll_lock = roll_lock;
+        self
+    }
+
+    /// Углы восстанавливаются из камеры, если её повернули извне (например, при загрузке сцены)
+    fn sync(&mut self, camera: &Camera) {
+        if self.rotation == Some(camera.rotation) {
+            return;
+        }
+
+        let forward = camera.forward();
+        self.yaw = (-forward.x).atan2(-forward.z);
+        self.pitch = forward.y.clamp(-1.0, 1.0).asin().clamp(-self.max_pitch, self.max_pitch);
+        self.roll = 0.0;
+        self.velocity = Vec3::ZERO;
+    }
+
+    fn rotation(&self) -> Quat {
+        let yaw = Quat::from_axis_angle(Vec3::Y, self.yaw);
+        let pitch = Quat::from_axis_angle(Vec3::X, self.pitch);
+        let roll = Quat::from_axis_angle(-Vec3::Z, self.roll);
+
+        (yaw * pitch * roll).normalize()
+    }
+}
+
+impl CameraController for FlyCameraController {
+    fn update(&mut self, engine: &mut Engine, dt: f32) {
+        let input = engine.input();
+
+        if self.grabbed && (input.action_pressed("release_cursor") || !input.focused()) {
+            self.grabbed = false;
+            grab_cursor(engine, false);
+        } else if !self.grabbed && input.action_pressed("grab_cursor") {
+            self.grabbed = true;
+            grab_cursor(engine, true);
+        }
+
+        let input = engine.input();
+        let direction = Vec3::new(input.axis("move_x"), input.axis("move_y"), input.axis("move_z"));
+
+        let mut look_x = input.axis_buttons("look_x") * self.look_speed * dt;
+        let mut look_y = input.axis_buttons("look_y") * self.look_speed * dt;
+        if self.grabbed {
+            look_x += input.axis_mouse("look_x") * self.sensitivity;
+            look_y += input.axis_mouse("look_y") * self.sensitivity;
+        }
+
+        let roll = input.axis_buttons("roll") * self.roll_speed * dt;
+        let fov = input.axis_buttons("fov") * self.fov_speed * dt + input.axis_mouse("fov");
+
+        let camera = engine.get_camera_mut();
+        self.sync(camera);
+
+        self.yaw -= look_x;
+        self.pitch = (self.pitch + look_y).clamp(-self.max_pitch, self.max_pitch);
+        self.roll = if self.roll_lock { 0.0 } else { self.roll + roll };
+
+        camera.rotation = self.rotation();
+        camera.fov = (camera.fov + fov).clamp(self.min_fov, self.max_fov);
+
+        let wish = camera.right() * direction.x + camera.up() * direction.y + camera.forward() * direction.z;
+        let target = if wish.length() > 0.0 { wish.normalize() * self.speed } else { Vec3::ZERO };
+        let rate = if wish.length() > 0.0 { self.acceleration } else { self.deceleration };
+
+        let difference = target - self.velocity;
+        let step = rate * dt;
+
+        self.velocity = if difference.length() <= step {
+            target
+        } else {
+            self.velocity + difference.normalize() * step
+        };
+
+        camera.position = camera.position + self.velocity * dt;
+        self.rotation = Some(camera.rotation);
+    }
+
+    fn on_deactivate(&mut self, engine: &mut Engine) {
+        if self.grabbed {
+            self.grabbed = false;
+            grab_cursor(engine, false);
+        }
+
+        self.rotation = None;
+    }
+}
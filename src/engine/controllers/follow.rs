@@ -0,0 +1,91 @@
+use crate::engine::controllers::controller::*;
+use crate::engine::core::primitives::*;
+use crate::engine::ecs::*;
+use crate::engine::engine::*;
+use crate::engine::render::transform::*;
+
+/// Слежение за сущностью с пружинным сглаживанием
+pub struct FollowCameraController {
+    pub target: Entity,
+    /// Положение камеры относительно цели в её системе координат
+    pub offset: Vec3,
+    /// Точка взгляда относительно цели
+    pub look_offset: Vec3,
+    /// Жёсткость пружины, 1/с²
+    pub stiffness: f32,
+    /// Коэффициент затухания, 1 - критическое
+    pub damping_ratio: f32,
+    /// Поворачивать смещение вместе с целью
+    pub rotate_with_target: bool,
+    velocity: Vec3,
+    position: Option<Vec3>
+}
+
+impl FollowCameraController {
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            offset: Vec3::new(0.0, 2.0, 5.0),
+            look_offset: Vec3::ZERO,
+            stiffness: 30.0,
+            damping_ratio: 1.0,
+            rotate_with_target: false,
+            velocity: Vec3::ZERO,
+            position: None
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_stiffness(mut self, stiffness: f32, damping_ratio: f32) -> Self {
+        self.stiffness = stiffness;
+        self.damping_ratio = damping_ratio;
+        self
+    }
+}
+
+impl CameraController for FollowCameraController {
+    fn update(&mut self, engine: &mut Engine, dt: f32) {
+        let Some(GlobalTransform(transform)) = engine.ecs.get::<GlobalTransform>(self.target) else {
+            return;
+        };
+
+        let offset = if self.rotate_with_target { transform.rotation * self.offset } else { self.offset };
+        let desired = transform.position + offset;
+        let look_at = transform.position + self.look_offset;
+
+        let camera = engine.get_camera_mut();
+        let mut position = self.position.unwrap_or(desired);
+
+        // Полунеявный Эйлер для пружины с затуханием
+        let damping = 2.0 * self.damping_ratio * self.stiffness.sqrt();
+        let acceleration = (desired - position) * self.stiffness - self.velocity * damping;
+        self.velocity = self.velocity + acceleration * dt;
+        position = position + self.velocity * dt;
+
+        camera.position = position;
+        camera.rotation = look_rotation(look_at - position);
+        self.position = Some(position);
+    }
+
+    fn on_deactivate(&mut self, _engine: &mut Engine) {
+        self.position = None;
+        self.velocity = Vec3::ZERO;
+    }
+}
+
+/// Поворот, направляющий -Z по направлению взгляда без крена
+pub fn look_rotation(direction: Vec3) -> Quat {
+    let direction = direction.normalize();
+    if direction.length() == 0.0 {
+        return Quat::IDENTITY;
+    }
+
+    let yaw = (-direction.x).atan2(-direction.z);
+    let pitch = direction.y.clamp(-1.0, 1.0).asin();
+
+    (Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch)).normalize()
+}
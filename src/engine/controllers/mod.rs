@@ -0,0 +1,4 @@
+pub mod controller;
+pub mod fly;
+pub mod orbit;
+pub mod follow;
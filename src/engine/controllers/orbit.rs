@@ -0,0 +1,104 @@
+use std::f32::consts::FRAC_PI_2;
+use crate::engine::controllers::controller::*;
+use crate::engine::core::primitives::*;
+use crate::engine::engine::*;
+use crate::engine::render::camera::*;
+
+/// Вращение вокруг точки: `orbit_rotate` (левая кнопка) - поворот,
+/// `orbit_pan` (средняя) - сдвиг, колесо - приближение
+pub struct OrbitCameraController {
+    pub target: Vec3,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Поворот на пиксель движения мыши, рад
+    pub sensitivity: f32,
+    /// Поворот стрелками, рад/с
+    pub look_speed: f32,
+    /// Сдвиг на пиксель, в долях расстояния до цели
+    pub pan_speed: f32,
+    /// Доля расстояния на одно деление колеса
+    pub zoom_speed: f32,
+    pub max_pitch: f32,
+    pub yaw: f32,
+    pub pitch: f32
+}
+
+impl Default for OrbitCameraController {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            distance: 5.0,
+            min_distance: 0.5,
+            max_distance: 100.0,
+            sensitivity: 0.005,
+            look_speed: 1.5,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            max_pitch: FRAC_PI_2 - 0.01,
+            yaw: 0.0,
+            pitch: -0.3
+        }
+    }
+}
+
+impl OrbitCameraController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            ..Default::default()
+        }
+    }
+
+    /// Орбита вокруг точки перед камерой без скачка изображения
+    pub fn from_camera(camera: &Camera, distance: f32) -> Self {
+        let forward = camera.forward();
+
+        Self {
+            target: camera.position + forward * distance,
+            distance,
+            yaw: (-forward.x).atan2(-forward.z),
+            pitch: forward.y.clamp(-1.0, 1.0).asin(),
+            ..Default::default()
+        }
+    }
+
+    pub fn rotation(&self) -> Quat {
+        let yaw = Quat::from_axis_angle(Vec3::Y, self.yaw);
+        let pitch = Quat::from_axis_angle(Vec3::X, self.pitch);
+
+        (yaw * pitch).normalize()
+    }
+}
+
+impl CameraController for OrbitCameraController {
+    fn update(&mut self, engine: &mut Engine, dt: f32) {
+        let input = engine.input();
+        let (dx, dy) = input.mouse_delta();
+
+        let mut look_x = input.axis_buttons("look_x") * self.look_speed * dt;
+        let mut look_y = input.axis_buttons("look_y") * self.look_speed * dt;
+        if input.action_down("orbit_rotate") {
+            look_x += dx * self.sensitivity;
+            look_y -= dy * self.sensitivity;
+        }
+
+        let pan = if input.action_down("orbit_pan") { (dx, dy) } else { (0.0, 0.0) };
+        let zoom = input.scroll() + input.axis_buttons("move_z") * dt * 5.0;
+
+        self.yaw -= look_x;
+        self.pitch = (self.pitch + look_y).clamp(-self.max_pitch, self.max_pitch);
+        self.distance = (self.distance * (1.0 - zoom * self.zoom_speed)).clamp(self.min_distance, self.max_distance);
+
+        let rotation = self.rotation();
+        let right = rotation * Vec3::X;
+        let up = rotation * Vec3::Y;
+        let pan_scale = self.pan_speed * self.distance;
+        self.target = self.target - right * (pan.0 * pan_scale) + up * (pan.1 * pan_scale);
+
+        let camera = engine.get_camera_mut();
+        camera.rotation = rotation;
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
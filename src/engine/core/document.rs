@@ -0,0 +1,341 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use crate::engine::core::primitives::*;
+
+/// Секция текстового документа
+#[derive(Clone, Debug, Default)]
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+    pub line: usize
+}
+
+impl Section {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: Vec::new(),
+            line: 0
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value))
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    pub fn get_f32(&self, key: &str) -> Result<Option<f32>, String> {
+        self.parse(key, parse_f32)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, String> {
+        self.parse(key, parse_bool)
+    }
+
+    pub fn get_vec3(&self, key: &str) -> Result<Option<Vec3>, String> {
+        self.parse(key, parse_vec3)
+    }
+
+    pub fn get_quat(&self, key: &str) -> Result<Option<Quat>, String> {
+        self.parse(key, parse_quat)
+    }
+
+    pub fn parse<T>(&self, key: &str, parser: impl Fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+        match self.get(key) {
+            Some(value) => parser(value)
+                .map(Some)
+                .map_err(|e| format!("[{}] (строка {}), ключ \"{}\": {}", self.name, self.line, key, e)),
+            None => Ok(None)
+        }
+    }
+}
+
+/// Текстовый документ из секций вида `[name]` с парами `key = value`.
+/// Секции с одинаковым именем могут повторяться, ключи внутри секции - нет.
+/// Строки с `#` - комментарии
+#[derive(Clone, Debug, Default)]
+pub struct Document {
+    pub sections: Vec<Section>
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut sections = Vec::new();
+        let mut current = Section::new("");
+
+        for (i, raw) in text.lines().enumerate() {
+            let line = raw.trim();
+            let number = i + 1;
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("Строка {}: незакрытая секция", number))?;
+
+                if !current.name.is_empty() || !current.entries.is_empty() {
+                    sections.push(current);
+                }
+
+                current = Section::new(name.trim());
+                current.line = number;
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Строка {}: ожидалось \"ключ = значение\"", number))?;
+
+            let key = key.trim();
+            if current.get(key).is_some() {
+                return Err(format!("Строка {}: ключ \"{}\" уже задан в [{}]", number, key, current.name));
+            }
+
+            current.entries.push((key.to_string(), value.trim().to_string()));
+        }
+
+        if !current.name.is_empty() || !current.entries.is_empty() {
+            sections.push(current);
+        }
+
+        Ok(Self { sections })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Не удалось прочитать {}: {}", path.display(), e))?;
+
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() && !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Не удалось создать {}: {}", parent.display(), e))?;
+        }
+
+        fs::write(path, self.to_string())
+            .map_err(|e| format!("Не удалось записать {}: {}", path.display(), e))
+    }
+
+    pub fn push(&mut self, section: Section) {
+        self.sections.push(section);
+    }
+
+    /// Первая секция с указанным именем
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Все секции с указанным именем
+    pub fn sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> + 'a {
+        self.sections.iter().filter(move |s| s.name == name)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, section) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            if !section.name.is_empty() {
+                writeln!(f, "[{}]", section.name)?;
+            }
+
+            for (key, value) in &section.entries {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Значения
+pub fn parse_f32(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("\"{}\" не число", value))
+}
+
+pub fn parse_u32(value: &str) -> Result<u32, String> {
+    value
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| format!("\"{}\" не целое неотрицательное число", value))
+}
+
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.trim() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        other => Err(format!("\"{}\" не логическое значение", other))
+    }
+}
+
+pub fn parse_floats<const N: usize>(value: &str) -> Result<[f32; N], String> {
+    let parts = value.split_whitespace().collect::<Vec<_>>();
+
+    if parts.len() != N {
+        return Err(format!("ожидалось {} чисел, получено {}", N, parts.len()));
+    }
+
+    let mut result = [0.0; N];
+    for (slot, part) in result.iter_mut().zip(parts) {
+        *slot = parse_f32(part)?;
+    }
+
+    Ok(result)
+}
+
+pub fn parse_vec3(value: &str) -> Result<Vec3, String> {
+    let [x, y, z] = parse_floats::<3>(value)?;
+    Ok(Vec3::new(x, y, z))
+}
+
+pub fn parse_quat(value: &str) -> Result<Quat, String> {
+    let [x, y, z, w] = parse_floats::<4>(value)?;
+    Ok(Quat::new(x, y, z, w).normalize())
+}
+
+pub fn format_vec3(value: Vec3) -> String {
+    format!("{} {} {}", value.x, value.y, value.z)
+}
+
+pub fn format_quat(value: Quat) -> String {
+    format!("{} {} {} {}", value.x, value.y, value.z, value.w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "\
+# Комментарий до первой секции
+[camera]
+position = 0 1 5
+
+  # Отступ перед комментарием
+[entity]
+  mesh   =   cube
+base_color = 0.5 0.25 1
+
+[entity]
+light = point
+";
+
+    #[test]
+    fn parses_sections_skipping_comments() {
+        let document = Document::parse(TEXT).unwrap();
+
+        assert_eq!(document.sections.len(), 3);
+        assert_eq!(document.section("camera").unwrap().line, 2);
+        assert_eq!(document.sections("entity").map(|s| s.line).collect::<Vec<_>>(), [6, 10]);
+
+        let entity = document.section("entity").unwrap();
+        assert_eq!(entity.get("mesh"), Some("cube"));
+        assert_eq!(entity.get_vec3("base_color").unwrap(), Some(Vec3::new(0.5, 0.25, 1.0)));
+        assert_eq!(entity.get("light"), None);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let document = Document::parse(TEXT).unwrap();
+        let reparsed = Document::parse(&document.to_string()).unwrap();
+
+        assert_eq!(reparsed.sections.len(), document.sections.len());
+        for (a, b) in document.sections.iter().zip(&reparsed.sections) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.entries, b.entries);
+        }
+    }
+
+    #[test]
+    fn duplicate_key_reports_its_line() {
+        let error = Document::parse("[entity]\nmesh = cube\n\nmesh = sphere\n").unwrap_err();
+
+        assert!(error.starts_with("Строка 4:"), "{}", error);
+        assert!(error.contains("mesh"), "{}", error);
+    }
+
+    #[test]
+    fn same_key_in_different_sections_is_allowed() {
+        let document = Document::parse("[entity]\nmesh = cube\n[entity]\nmesh = sphere\n").unwrap();
+        assert_eq!(document.sections("entity").filter_map(|s| s.get("mesh")).collect::<Vec<_>>(), ["cube", "sphere"]);
+    }
+
+    #[test]
+    fn asset_files_have_no_duplicate_keys() {
+        for dir in ["assets/scenes", "assets/prefabs"] {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if let Err(e) = Document::load(&path) {
+                    panic!("{}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn syntax_errors_report_their_line() {
+        assert!(Document::parse("[entity]\nmesh cube\n").unwrap_err().starts_with("Строка 2:"));
+        assert!(Document::parse("# x\n[entity\n").unwrap_err().starts_with("Строка 2:"));
+    }
+
+    #[test]
+    fn bad_values_name_section_line_and_key() {
+        let document = Document::parse("\n[entity]\nposition = 1 2\nscale = 1 x 1\nroughness = soft\nshadows = maybe\n").unwrap();
+        let section = document.section("entity").unwrap();
+
+        let error = section.get_vec3("position").unwrap_err();
+        assert!(error.starts_with("[entity] (строка 2), ключ \"position\""), "{}", error);
+        assert!(error.contains("ожидалось 3 чисел, получено 2"), "{}", error);
+
+        assert!(section.get_vec3("scale").unwrap_err().contains("\"x\" не число"));
+        assert!(section.get_f32("roughness").unwrap_err().contains("\"soft\" не число"));
+        assert!(section.get_bool("shadows").is_err());
+        assert!(section.get_quat("rotation").unwrap().is_none());
+    }
+
+    #[test]
+    fn values_format_and_parse_back() {
+        let value = Vec3::new(1.5, -0.25, 100.0);
+        assert_eq!(parse_vec3(&format_vec3(value)).unwrap(), value);
+
+        let rotation = parse_quat("0 0 0 2").unwrap();
+        assert_eq!(rotation.w, 1.0);
+        assert_eq!(parse_u32(" 7 ").unwrap(), 7);
+        assert!(parse_u32("-1").is_err());
+        assert_eq!(parse_bool("yes"), Ok(true));
+    }
+}
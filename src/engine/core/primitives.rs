@@ -119,7 +119,7 @@ fn clean_f32(value: f32) -> f32 {
 
 /// Трёхмерный вектор
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -554,7 +554,8 @@ impl Mul for Mat3 {
     }
 }
 
-/// Четырёхмерная матрица
+/// Четырёхмерная матрица. Хранится по столбцам, как в WGSL: `data[i]` - i-й столбец.
+/// Глубина после проекции лежит в [0, 1], как принято в wgpu
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Mat4 {
@@ -586,9 +587,9 @@ impl Mat4 {
 
         Self {
             data: [
-                [rot.data[0][0] * transform.scale.x,  rot.data[0][1] * transform.scale.x,  rot.data[0][2] * transform.scale.x,  0.0],
-                [rot.data[1][0] * transform.scale.y,  rot.data[1][1] * transform.scale.y,  rot.data[1][2] * transform.scale.y,  0.0],
-                [rot.data[2][0] * transform.scale.z,  rot.data[2][1] * transform.scale.z,  rot.data[2][2] * transform.scale.z,  0.0],
+                [rot.data[0][0] * transform.scale.x,  rot.data[1][0] * transform.scale.x,  rot.data[2][0] * transform.scale.x,  0.0],
+                [rot.data[0][1] * transform.scale.y,  rot.data[1][1] * transform.scale.y,  rot.data[2][1] * transform.scale.y,  0.0],
+                [rot.data[0][2] * transform.scale.z,  rot.data[1][2] * transform.scale.z,  rot.data[2][2] * transform.scale.z,  0.0],
                 [transform.position.x,                transform.position.y,                transform.position.z,                1.0]
             ]
         }
@@ -611,10 +612,10 @@ impl Mat4 {
         let u = s.cross(f);
 
         Mat4::new([
-            [s.x,   s.y,   s.z,   -s.dot(eye)],
-            [u.x,   u.y,   u.z,   -u.dot(eye)],
-            [-f.x,  -f.y,  -f.z,  -f.dot(eye)],
-            [0.0,   0.0,   0.0,   1.0        ]
+            [s.x,          u.x,          -f.x,        0.0],
+            [s.y,          u.y,          -f.y,        0.0],
+            [s.z,          u.z,          -f.z,        0.0],
+            [-s.dot(eye),  -u.dot(eye),  f.dot(eye),  1.0]
         ])
     }
 
@@ -624,10 +625,23 @@ impl Mat4 {
         let nf = near - far;
 
         Mat4::new([
-            [f / aspect,  0.0,  0.0,                      0.0 ],
-            [0.0,         f,    0.0,                      0.0 ],
-            [0.0,         0.0,  (far + near) / nf,        -1.0],
-            [0.0,         0.0,  (2.0 * far * near) / nf,  0.0 ]
+            [f / aspect,  0.0,  0.0,                0.0 ],
+            [0.0,         f,    0.0,                0.0 ],
+            [0.0,         0.0,  far / nf,           -1.0],
+            [0.0,         0.0,  (far * near) / nf,  0.0 ]
+        ])
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let rl = right - left;
+        let tb = top - bottom;
+        let nf = near - far;
+
+        Mat4::new([
+            [2.0 / rl,                0.0,                     0.0,         0.0],
+            [0.0,                     2.0 / tb,                0.0,         0.0],
+            [0.0,                     0.0,                     1.0 / nf,    0.0],
+            [-(right + left) / rl,    -(top + bottom) / tb,    near / nf,   1.0]
         ])
     }
 
@@ -755,32 +769,122 @@ impl Mat4 {
 
         det = 1.0 / det;
 
-        let mut result = Mat4::default();
-        for i in 0..4 {
-            for j in 0..4 {
-                result.data[i][j] = inv[i][j] * det;
-            }
-        }
-        
-        result
+        Mat4::new(inv.map(|column| column.map(|value| value * det)))
     }
 }
 
 impl Mul for Mat4 {
     type Output = Mat4;
+    /// `a * b` применяет сначала `b`, затем `a`
     fn mul(self, rhs: Self) -> Self::Output {
         let mut result = Mat4::default();
 
-        for row in 0..4 {
-            for col in 0..4 {
-                result.data[row][col] =
-                    self.data[row][0] * rhs.data[0][col] +
-                    self.data[row][1] * rhs.data[1][col] +
-                    self.data[row][2] * rhs.data[2][col] +
-                    self.data[row][3] * rhs.data[3][col];
+        for col in 0..4 {
+            for row in 0..4 {
+                result.data[col][row] =
+                    self.data[0][row] * rhs.data[col][0] +
+                    self.data[1][row] * rhs.data[col][1] +
+                    self.data[2][row] * rhs.data[col][2] +
+                    self.data[3][row] * rhs.data[col][3];
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(m: &Mat4, v: [f32; 4]) -> [f32; 4] {
+        let mut result = [0.0; 4];
+        for (col, value) in v.iter().enumerate() {
+            for (row, out) in result.iter_mut().enumerate() {
+                *out += m.data[col][row] * value;
             }
         }
 
         result
     }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn perspective_maps_near_to_zero_and_far_to_one() {
+        let projection = Mat4::perspective(90.0, 1.0, 0.1, 100.0);
+
+        let near = apply(&projection, [0.0, 0.0, -0.1, 1.0]);
+        let far = apply(&projection, [0.0, 0.0, -100.0, 1.0]);
+
+        assert_close(near[2] / near[3], 0.0);
+        assert_close(far[2] / far[3], 1.0);
+        assert!(near[3] > 0.0, "камера смотрит вдоль -Z");
+    }
+
+    #[test]
+    fn orthographic_maps_near_to_zero_and_far_to_one() {
+        let projection = Mat4::orthographic(-1.0, 1.0, -1.0, 1.0, 0.5, 20.0);
+
+        assert_close(apply(&projection, [0.0, 0.0, -0.5, 1.0])[2], 0.0);
+        assert_close(apply(&projection, [0.0, 0.0, -20.0, 1.0])[2], 1.0);
+        assert_close(apply(&projection, [1.0, -1.0, -1.0, 1.0])[0], 1.0);
+        assert_close(apply(&projection, [1.0, -1.0, -1.0, 1.0])[1], -1.0);
+    }
+
+    #[test]
+    fn columns_hold_basis_and_translation() {
+        let transform = Transform::new(Vec3::new(1.0, 2.0, 3.0), Quat::IDENTITY, Vec3::new(2.0, 3.0, 4.0));
+        let m = Mat4::from_transform(&transform);
+
+        assert_eq!(m.data[0], [2.0, 0.0, 0.0, 0.0]);
+        assert_eq!(m.data[1], [0.0, 3.0, 0.0, 0.0]);
+        assert_eq!(m.data[2], [0.0, 0.0, 4.0, 0.0]);
+        assert_eq!(m.data[3], [1.0, 2.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn mul_applies_right_operand_first() {
+        let translate = Mat4::from_transform(&Transform::new(Vec3::X, Quat::IDENTITY, Vec3::IDENTITY));
+        let scale = Mat4::from_transform(&Transform::new(Vec3::ZERO, Quat::IDENTITY, Vec3::IDENTITY * 2.0));
+
+        assert_close(apply(&(translate * scale), [1.0, 0.0, 0.0, 1.0])[0], 3.0);
+        assert_close(apply(&(scale * translate), [1.0, 0.0, 0.0, 1.0])[0], 4.0);
+    }
+
+    #[test]
+    fn rotation_follows_right_hand_rule() {
+        let rotation = Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2);
+        let m = Mat4::from_transform(&Transform::new(Vec3::ZERO, rotation, Vec3::IDENTITY));
+        let x = apply(&m, [1.0, 0.0, 0.0, 0.0]);
+
+        assert_close(x[0], 0.0);
+        assert_close(x[2], -1.0);
+    }
+
+    #[test]
+    fn look_at_puts_target_on_negative_z() {
+        let view = Mat4::look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let target = apply(&view, [0.0, 0.0, 0.0, 1.0]);
+        let up = apply(&view, [0.0, 1.0, 5.0, 1.0]);
+
+        assert_close(target[0], 0.0);
+        assert_close(target[2], -5.0);
+        assert_close(up[1], 1.0);
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let rotation = Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0).normalize(), 0.7);
+        let m = Mat4::from_transform(&Transform::new(Vec3::new(1.0, -2.0, 3.0), rotation, Vec3::new(1.0, 2.0, 0.5)));
+        let identity = m.inverse() * m;
+
+        for col in 0..4 {
+            for row in 0..4 {
+                assert_close(identity.data[col][row], if col == row { 1.0 } else { 0.0 });
+            }
+        }
+    }
 }
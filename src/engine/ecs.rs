@@ -1,50 +1,289 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use crate::engine::components::*;
+use crate::engine::events::*;
 use crate::engine::core::primitives::*;
 use crate::engine::render::camera::*;
+use crate::engine::render::material::*;
 use crate::engine::render::mesh::*;
 use crate::engine::render::renderable::*;
-use crate::engine::render::renderer::*;
 use crate::engine::render::transform::*;
+use crate::engine::resources::*;
+use crate::engine::script::*;
 
-pub type Entity = usize;
+/// Сущность: индекс слота и поколение. После удаления слот переиспользуется
+/// с новым поколением, поэтому старые идентификаторы перестают быть живыми
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32
+}
+
+impl Entity {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+/// Разбор записи вида `3v1`, как её выводит `Display`
+impl FromStr for Entity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, generation) = s
+            .split_once('v')
+            .and_then(|(i, g)| Some((i.parse().ok()?, g.parse().ok()?)))
+            .ok_or_else(|| format!("\"{}\" не сущность", s))?;
+
+        Ok(Entity { index, generation })
+    }
+}
+
+/// Выдача и переиспользование идентификаторов
+#[derive(Default)]
+pub struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    count: usize
+}
+
+impl Entities {
+    pub fn create(&mut self) -> Entity {
+        self.count += 1;
+
+        if let Some(index) = self.free.pop() {
+            self.alive[index as usize] = true;
+            return Entity { index, generation: self.generations[index as usize] };
+        }
+
+        let index = self.generations.len() as u32;
+        self.generations.push(0);
+        self.alive.push(true);
+
+        Entity { index, generation: 0 }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        index < self.alive.len() && self.alive[index] && self.generations[index] == entity.generation
+    }
+
+    /// Возвращает false, если сущность уже удалена
+    pub fn delete(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let index = entity.index as usize;
+        self.alive[index] = false;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free.push(entity.index);
+        self.count -= 1;
+
+        true
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive
+            .iter()
+            .enumerate()
+            .filter(|(_, alive)| **alive)
+            .map(|(index, _)| Entity { index: index as u32, generation: self.generations[index] })
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
 
 pub struct ECS {
-    next_entity: Entity,
-    pub camera: Option<Camera>,
-    pub transforms: HashMap<Entity, Transform>,
-    pub meshes: HashMap<Entity, Mesh>,
-    pub renderables: HashMap<Entity, RenderableMesh>,
-    pub lights: HashMap<Entity, Light>
+    pub entities: Entities,
+    pub components: Components,
+    pub resources: Resources,
+    pub events: EventBus,
+    pub scripts: HashMap<Entity, Vec<ScriptSlot>>,
+    frame_tick: u32
+}
+
+impl Default for ECS {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ECS {
     pub fn new() -> Self {
+        let mut resources = Resources::new();
+        resources.insert(Camera::default());
+
         Self {
-            next_entity: 0,
-            camera: Some(Camera::default()),
-            transforms: HashMap::new(),
-            meshes: HashMap::new(),
-            renderables: HashMap::new(),
-            lights: HashMap::new()
+            entities: Entities::default(),
+            components: Components::new(),
+            resources,
+            events: EventBus::new(),
+            scripts: HashMap::new(),
+            frame_tick: 0
         }
     }
 
+    /// Resources
+    pub fn insert_resource<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.resources.insert(value)
+    }
+
+    pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+        self.resources.remove()
+    }
+
+    pub fn get_resource<T: 'static>(&self) -> Option<&T> {
+        self.resources.get()
+    }
+
+    pub fn get_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut()
+    }
+
+    /// Для ресурсов, без которых движок не работает
+    pub fn resource<T: 'static>(&self) -> &T {
+        self.resources.get().unwrap_or_else(|| panic!("Нет ресурса {}", std::any::type_name::<T>()))
+    }
+
+    pub fn resource_mut<T: 'static>(&mut self) -> &mut T {
+        self.resources.get_mut().unwrap_or_else(|| panic!("Нет ресурса {}", std::any::type_name::<T>()))
+    }
+
+    pub fn has_resource<T: 'static>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
     /// Camera
     pub fn get_camera_mut(&mut self) -> &mut Camera {
-        self.camera.as_mut().expect("Камеры нет")
+        self.resource_mut::<Camera>()
+    }
+
+    /// Components
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        if self.is_alive(entity) {
+            self.components.insert(entity, component);
+        }
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.components.get(entity)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.components.get_mut(entity)
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.components.remove(entity)
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.components.has::<T>(entity)
+    }
+
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.components.query::<T>()
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.components.query_mut::<T>()
+    }
+
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        self.components.query2::<A, B>()
+    }
+
+    pub fn query3<A: 'static, B: 'static, C: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B, &C)> {
+        self.components.query3::<A, B, C>()
+    }
+
+    pub fn each2_mut<A: 'static, B: 'static>(&mut self, f: impl FnMut(Entity, &mut A, &B)) {
+        self.components.each2_mut::<A, B>(f);
+    }
+
+    /// Change detection. Система запоминает значение `change_tick` после
+    /// обработки и в следующий раз спрашивает об изменениях новее него
+    pub fn change_tick(&mut self) -> u32 {
+        self.components.change_tick()
+    }
+
+    pub fn added<T: 'static>(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.components.added::<T>(since)
+    }
+
+    pub fn changed<T: 'static>(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.components.changed::<T>(since)
+    }
+
+    /// Удаления хранятся до конца следующего кадра
+    pub fn removed<T: 'static>(&self, since: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.components.removed::<T>(since)
+    }
+
+    pub fn is_changed<T: 'static>(&self, entity: Entity, since: u32) -> bool {
+        self.components.is_changed::<T>(entity, since)
+    }
+
+    /// Начало кадра: забываются удаления, сделанные до прошлого кадра
+    pub fn begin_frame(&mut self) {
+        let tick = self.change_tick();
+        self.components.forget_removed(self.frame_tick);
+        self.frame_tick = tick;
     }
 
     /// Light
+    /// Положение и направление берутся из мирового позиционирования сущности,
+    /// до первого распространения - из локального. Источники идут по порядку
+    /// сущностей, чтобы слои теней не переназначались между кадрами
     pub fn collect_lights(&self) -> Vec<Light> {
-        self.lights.values().cloned().collect()
+        let mut lights = self.query::<Light>().collect::<Vec<_>>();
+        lights.sort_by_key(|(entity, _)| *entity);
+
+        lights.into_iter()
+            .map(|(entity, light)| {
+                let mut light = *light;
+                let transform = self.get::<GlobalTransform>(entity)
+                    .map(|global| global.0.clone())
+                    .or_else(|| self.get::<Transform>(entity).cloned());
+
+                if let Some(transform) = transform {
+                    light.position = transform.position;
+                    light.direction = (transform.rotation * -Vec3::Z).normalize();
+                }
+                light
+            })
+            .collect()
     }
 
     pub fn add_light(&mut self, entity: Entity, light: Light) {
-        self.lights.insert(entity, light);
+        self.insert(entity, light);
     }
-    
+
+    pub fn get_light_mut(&mut self, entity: &Entity) -> Option<&mut Light> {
+        self.get_mut::<Light>(*entity)
+    }
+
     pub fn edit_light(&mut self, entity: &Entity, color: Vec3, intensity: f32, range: f32) {
-        if let Some(obj_light) = self.lights.get_mut(&entity) {
+        if let Some(obj_light) = self.get_mut::<Light>(*entity) {
             obj_light.color = color;
             obj_light.intensity = intensity;
             obj_light.range = range;
@@ -53,53 +292,143 @@ impl ECS {
 
     /// Entity
     pub fn create_entity(&mut self) -> Entity {
-        let id = self.next_entity;
-        self.next_entity += 1;
-        id
+        let entity = self.entities.create();
+        self.events.send(EntitySpawned(entity));
+        entity
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    /// Удаление сущности со всеми компонентами и потомками. Возвращает false для уже удалённой
+    pub fn delete_entity(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        self.remove_parent(entity);
+
+        for e in self.descendants(entity) {
+            self.entities.delete(e);
+            self.components.remove_entity(e);
+            self.scripts.remove(&e);
+            self.events.send(EntityDespawned(e));
+        }
+
+        true
     }
 
-    pub fn delete_entity(&mut self, entity: Entity) {
-        self.transforms.remove(&entity);
-        self.meshes.remove(&entity);
+    /// Удаление всех сущностей и сброс камеры. Остальные ресурсы сохраняются
+    pub fn clear(&mut self) {
+        let entities = self.entities.iter().collect::<Vec<_>>();
+        for entity in entities {
+            self.entities.delete(entity);
+            self.events.send(EntityDespawned(entity));
+        }
+
+        self.scripts.clear();
+        self.components.clear();
+        self.insert_resource(Camera::default());
     }
 
     /// Transform
     pub fn add_transform(&mut self, entity: Entity, transform: Transform) {
-        self.transforms.insert(entity, transform);
+        self.insert(entity, GlobalTransform(transform.clone()));
+        self.insert(entity, transform);
     }
 
     pub fn transform(&mut self, entity: &Entity, transform: Transform) {
-        if let Some(obj_transform) = self.transforms.get_mut(entity) {
+        if let Some(obj_transform) = self.get_mut::<Transform>(*entity) {
             *obj_transform = transform;
         }
     }
 
+    pub fn get_transform(&self, entity: &Entity) -> Option<&Transform> {
+        self.get::<Transform>(*entity)
+    }
+
+    pub fn get_transform_mut(&mut self, entity: &Entity) -> Option<&mut Transform> {
+        self.get_mut::<Transform>(*entity)
+    }
+
     /// Mesh
-    pub fn add_mesh(&mut self, entity: Entity, mesh: Mesh, renderer: &Renderer) {
-        self.meshes.insert(entity, mesh.clone());
-
-        let renderable = RenderableMesh::new(
-            &renderer.device,
-            &renderer.render_pipeline.get_bind_group_layout(0),
-            &renderer.shadow_pipeline.get_bind_group_layout(0),
-            &mesh,
-            &renderer.shadow_cube_view, 
-            &renderer.shadow_sampler,
-            &renderer.light_buffer,
-            &renderer.light_count_buffer
-        );
-
-        self.renderables.insert(entity, renderable);
+    /// Буферы на видеокарте создаёт рендерер, когда замечает новую или изменённую сетку
+    pub fn add_mesh(&mut self, entity: Entity, mesh: Mesh) {
+        self.insert(entity, mesh);
+    }
+
+    pub fn add_material(&mut self, entity: Entity, material: Material) {
+        self.insert(entity, material);
     }
     
-    // TODO: добавить скрипты
-    // pub fn add_script(&mut self, entity: Entity, script: Box<dyn Updatable>) {
-    //     self.scripts.insert(entity, script);
-    // }
-    // 
-    // pub fn update(&mut self, dt: f32, engine: &mut Engine) {
-    //     for script in &mut self.scripts {
-    //         script.1.update(dt, engine);
-    //     }
-    // }
+    /// Script
+    pub fn add_script(&mut self, entity: Entity, script: Box<dyn Script>) {
+        if self.is_alive(entity) {
+            self.scripts.entry(entity).or_default().push(ScriptSlot::new(script));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_slots_are_reused_with_new_generation() {
+        let mut entities = Entities::default();
+        let first = entities.create();
+        let second = entities.create();
+
+        assert!(entities.delete(first));
+        assert!(!entities.delete(first));
+        assert!(!entities.is_alive(first));
+
+        let reused = entities.create();
+        assert_eq!(reused.index(), first.index());
+        assert_eq!(reused.generation(), first.generation() + 1);
+        assert_ne!(reused, first);
+        assert!(entities.is_alive(reused));
+        assert!(!entities.is_alive(first));
+
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities.iter().collect::<Vec<_>>(), [reused, second]);
+    }
+
+    #[test]
+    fn entity_parses_from_its_display() {
+        let mut entities = Entities::default();
+        let first = entities.create();
+        entities.delete(first);
+        let reused = entities.create();
+
+        assert_eq!(reused.to_string().parse::<Entity>(), Ok(reused));
+        assert!("3".parse::<Entity>().is_err());
+        assert!("av1".parse::<Entity>().is_err());
+    }
+
+    #[test]
+    fn stale_handle_does_not_delete_new_entity() {
+        let mut entities = Entities::default();
+        let old = entities.create();
+        entities.delete(old);
+        let new = entities.create();
+
+        assert!(!entities.delete(old));
+        assert!(entities.is_alive(new));
+        assert_eq!(entities.len(), 1);
+    }
+
+    #[test]
+    fn despawn_removes_components_of_the_entity_only() {
+        let mut ecs = ECS::new();
+        let (a, b) = (ecs.create_entity(), ecs.create_entity());
+        ecs.insert(a, 1u32);
+        ecs.insert(b, 2u32);
+
+        ecs.delete_entity(a);
+
+        assert!(!ecs.has::<u32>(a));
+        assert_eq!(ecs.get::<u32>(b), Some(&2));
+    }
 }
@@ -1,36 +1,323 @@
-use std::collections::HashSet;
-use winit::keyboard::PhysicalKey;
+use std::any::Any;
+use std::collections::HashMap;
 use std::sync::*;
+use winit::dpi::PhysicalSize;
+use winit::event::WindowEvent;
 use winit::window::*;
 use crate::engine::core::primitives::Vec3;
+use crate::engine::controllers::controller::*;
 use crate::engine::ecs::*;
+use crate::engine::events::*;
+use crate::engine::input::input::*;
+use crate::engine::objects;
+use crate::engine::prefab::*;
 use crate::engine::render::camera::*;
+use crate::engine::render::material::*;
 use crate::engine::render::mesh::*;
 use crate::engine::render::renderable::*;
 use crate::engine::render::renderer::*;
+use crate::engine::render::settings::*;
 use crate::engine::render::transform::*;
+use crate::engine::schedule::*;
+use crate::engine::scene::file;
+use crate::engine::scene::manager::*;
+use crate::engine::scene::snapshot::{self, *};
+use crate::engine::script::*;
+use crate::engine::time::*;
 
 pub struct Engine<'a> {
     pub window: Arc<Window>,
     pub renderer: Renderer<'a>,
     pub ecs: ECS,
-    pub pressed_keys: HashSet<PhysicalKey>
+    pub scenes: SceneManager,
+    pub schedule: Schedule,
+    camera_controller: Option<Box<dyn CameraController>>,
+    /// Сущности, удалённые во время обхода скриптов. Удаляются после возврата из скрипта
+    pending_deletions: Option<Vec<Entity>>
 }
 
 impl<'a> Engine<'a> {
     pub fn new(window: Arc<Window>) -> Self {
         let renderer = pollster::block_on(Renderer::new(window.clone()));
-        let ecs = ECS::new();
-        let pressed_keys = HashSet::new();
+        let mut ecs = ECS::new();
+        ecs.insert_resource(Input::default());
+        ecs.insert_resource(Time::new());
+        ecs.insert_resource(RenderSettings::default());
+        let scenes = SceneManager::new();
 
-        Self {
+        let mut engine = Self {
             window,
             renderer,
             ecs,
-            pressed_keys
+            scenes,
+            schedule: Schedule::new(),
+            camera_controller: None,
+            pending_deletions: None
+        };
+
+        let mut resized = EventReader::<WindowResized>::new();
+        engine.add_system(
+            System::new(WINDOW_RESIZE_SYSTEM, move |engine| {
+                if let Some(size) = engine.ecs.events.read(&mut resized).last().copied() {
+                    engine.renderer.resize(PhysicalSize::new(size.width, size.height));
+                }
+            })
+                .in_stage(Stage::Input)
+        );
+        engine.add_system(
+            System::new(SCRIPTS_FIXED_SYSTEM, |engine| engine.update_scripts(engine.time().fixed_delta, true))
+                .in_stage(Stage::FixedUpdate)
+        );
+        engine.add_system(
+            System::new(SCRIPTS_SYSTEM, |engine| engine.update_scripts(engine.time().delta(), false))
+                .in_stage(Stage::Update)
+        );
+        engine.add_system(
+            System::new(TRANSFORM_PROPAGATION_SYSTEM, |engine| engine.ecs.propagate_transforms())
+                .in_stage(Stage::PostUpdate)
+        );
+        // Камера управляется и во время паузы
+        engine.add_system(
+            System::new(CAMERA_CONTROLLER_SYSTEM, |engine| engine.update_camera_controller(engine.time().real_delta()))
+                .in_stage(Stage::PostUpdate)
+                .after(TRANSFORM_PROPAGATION_SYSTEM)
+        );
+
+        objects::register_prefabs(&mut engine);
+
+        engine
+    }
+
+    /// Кадр: стадии расписания по порядку, фиксированная стадия - по числу
+    /// накопленных шагов. Отрисовка вызывается отдельно
+    pub fn update(&mut self) {
+        self.time_mut().tick();
+        self.ecs.begin_frame();
+        self.ecs.events.update();
+
+        if let Some(name) = self.scenes.take_request() {
+            self.load_scene(&name);
+        }
+
+        self.run_stage(Stage::Input);
+        self.run_stage(Stage::PreUpdate);
+
+        while self.time_mut().expend_fixed_step() {
+            self.run_stage(Stage::FixedUpdate);
+        }
+
+        self.run_stage(Stage::Update);
+        self.run_stage(Stage::PostUpdate);
+        self.run_stage(Stage::RenderPrep);
+
+        self.input_mut().end_frame();
+    }
+
+    /// Resources
+    pub fn input(&self) -> &Input {
+        self.ecs.resource()
+    }
+
+    pub fn input_mut(&mut self) -> &mut Input {
+        self.ecs.resource_mut()
+    }
+
+    pub fn time(&self) -> &Time {
+        self.ecs.resource()
+    }
+
+    pub fn time_mut(&mut self) -> &mut Time {
+        self.ecs.resource_mut()
+    }
+
+    pub fn render_settings_mut(&mut self) -> &mut RenderSettings {
+        self.ecs.resources.get_or_insert_with(RenderSettings::default)
+    }
+
+    /// Ввод и события окна для систем
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        self.input_mut().handle_window_event(event);
+
+        match event {
+            WindowEvent::Resized(size) => {
+                self.send_event(WindowResized { width: size.width, height: size.height });
+            }
+            WindowEvent::Focused(focused) => {
+                self.send_event(WindowFocused(*focused));
+            }
+            _ => {}
+        }
+    }
+
+    /// Events
+    pub fn send_event<T: 'static>(&mut self, event: T) {
+        self.ecs.events.send(event);
+    }
+
+    /// Все события типа за этот и прошлый кадр
+    pub fn events<T: 'static>(&self) -> impl Iterator<Item = &T> {
+        self.ecs.events.iter()
+    }
+
+    /// Systems
+    /// Система с циклическими ограничениями порядка не добавляется
+    pub fn add_system(&mut self, system: System) {
+        if let Err(e) = self.schedule.add(system) {
+            eprintln!("{}", e);
+        }
+    }
+
+    pub fn remove_system(&mut self, name: &str) {
+        self.schedule.remove(name);
+    }
+
+    pub fn run_stage(&mut self, stage: Stage) {
+        let mut systems = self.schedule.take(stage);
+
+        for system in &mut systems {
+            system.run(self);
+        }
+
+        self.schedule.put_back(stage, systems);
+    }
+
+    /// Scenes
+    pub fn register_scene(&mut self, name: &str, scene: impl Scene + 'static) {
+        self.scenes.register(name, scene);
+    }
+
+    /// Смена сцены в начале следующего кадра. Безопасно вызывать из игровой логики
+    pub fn switch_scene(&mut self, name: &str) {
+        self.scenes.request(name);
+    }
+
+    /// Немедленная выгрузка текущей сцены и загрузка новой.
+    /// Повторная загрузка текущей сцены тоже проходит через `on_exit`
+    pub fn load_scene(&mut self, name: &str) {
+        if !self.scenes.contains(name) {
+            eprintln!("Сцена \"{}\" не зарегистрирована", name);
+            return;
+        }
+
+        let previous = self.scenes.current().map(str::to_string);
+        self.unload_scene();
+
+        let Some(mut next) = self.scenes.take(name) else {
+            return;
+        };
+
+        next.load(self);
+        next.on_enter(self);
+
+        self.scenes.put_back(name, next);
+        self.scenes.set_current(Some(name));
+        self.send_event(SceneChanged { previous, current: name.to_string() });
+    }
+
+    pub fn unload_scene(&mut self) {
+        if let Some(current) = self.scenes.current().map(str::to_string)
+            && let Some(mut scene) = self.scenes.take(&current) {
+            scene.on_exit(self);
+            self.scenes.put_back(&current, scene);
         }
+
+        self.scenes.set_current(None);
+        self.destroy_scripts();
+        self.ecs.clear();
+        self.renderer.textures.clear();
+    }
+
+    /// Добавление сущностей из файла сцены в текущий мир
+    pub fn load_scene_file(&mut self, path: &str) -> Result<Vec<Entity>, String> {
+        file::load_file(self, path)
+    }
+
+    /// Сохранение текущего мира в файл сцены
+    pub fn save_scene_file(&self, path: &str) -> Result<(), String> {
+        file::save_file(self, path)
+    }
+
+    /// Prefabs
+    pub fn spawn(&mut self, prefab: &Prefab) -> Entity {
+        prefab.instantiate(self, &Overrides::default())
+    }
+
+    pub fn spawn_with(&mut self, prefab: &Prefab, overrides: Overrides) -> Entity {
+        prefab.instantiate(self, &overrides)
+    }
+
+    pub fn register_prefab(&mut self, prefab: Prefab) {
+        self.ecs.resources.get_or_insert_with(Prefabs::default).register(prefab);
+    }
+
+    /// Регистрация шаблона из файла под его именем
+    pub fn load_prefab(&mut self, path: &str) -> Result<(), String> {
+        let prefab = Prefab::load(path)?;
+        self.register_prefab(prefab);
+        Ok(())
+    }
+
+    /// Экземпляр зарегистрированного шаблона
+    pub fn spawn_prefab(&mut self, name: &str, overrides: Overrides) -> Option<Entity> {
+        let prefab = self.ecs.get_resource::<Prefabs>()?.get(name)?.clone();
+        Some(prefab.instantiate(self, &overrides))
+    }
+
+    /// Snapshots
+    /// Компонент будет сохраняться в снимках мира
+    pub fn register_persistent<T: Persistent>(&mut self) {
+        self.ecs.resources.get_or_insert_with(PersistentComponents::default).register::<T>();
+    }
+
+    /// Скрипт будет сохраняться в снимках мира вместе с флагом запуска
+    pub fn register_persistent_script<T: Persistent + Script>(&mut self) {
+        self.ecs.resources.get_or_insert_with(PersistentComponents::default).register_script::<T>();
+    }
+
+    pub fn save_snapshot(&self, path: &str) -> Result<(), String> {
+        snapshot::save_file(self, path)
+    }
+
+    /// Заменяет текущий мир сохранённым
+    pub fn load_snapshot(&mut self, path: &str) -> Result<Vec<Entity>, String> {
+        snapshot::load_file(self, path)
     }
     
+    /// Camera
+    pub fn set_camera_controller(&mut self, controller: impl CameraController + 'static) {
+        self.replace_camera_controller(Some(Box::new(controller)));
+    }
+
+    pub fn clear_camera_controller(&mut self) {
+        self.replace_camera_controller(None);
+    }
+
+    /// Установлен ли сейчас контроллер камеры типа `T`
+    pub fn has_camera_controller<T: CameraController>(&self) -> bool {
+        self.camera_controller
+            .as_deref()
+            .is_some_and(|controller| (controller as &dyn Any).is::<T>())
+    }
+
+    fn replace_camera_controller(&mut self, controller: Option<Box<dyn CameraController>>) {
+        if let Some(mut previous) = self.camera_controller.take() {
+            previous.on_deactivate(self);
+        }
+
+        self.camera_controller = controller;
+    }
+
+    fn update_camera_controller(&mut self, dt: f32) {
+        if let Some(mut controller) = self.camera_controller.take() {
+            controller.update(self, dt);
+
+            // Контроллер мог заменить сам себя
+            if self.camera_controller.is_none() {
+                self.camera_controller = Some(controller);
+            }
+        }
+    }
+
     /// ECS - Entity
     pub fn get_camera_mut(&mut self) -> &mut Camera {
         self.ecs.get_camera_mut()
@@ -40,10 +327,53 @@ impl<'a> Engine<'a> {
         self.ecs.create_entity()
     }
 
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.ecs.is_alive(entity)
+    }
+
+    /// Удаление сущности с потомками и всеми компонентами, включая GPU-ресурсы и свет.
+    /// `on_destroy` скриптов вызывается до удаления, пока компоненты ещё на месте.
+    /// Во время обхода скриптов удаление откладывается до возврата из текущего скрипта
     pub fn delete_entity(&mut self, entity: Entity) {
+        if !self.ecs.is_alive(entity) {
+            return;
+        }
+
+        match &mut self.pending_deletions {
+            Some(pending) => {
+                if !pending.contains(&entity) {
+                    pending.push(entity);
+                }
+            }
+            None => self.destroy_entity(entity, &mut HashMap::new())
+        }
+    }
+
+    /// `running` - скрипты, вынутые из ECS на время обхода
+    fn destroy_entity(&mut self, entity: Entity, running: &mut HashMap<Entity, Vec<ScriptSlot>>) {
+        for e in self.ecs.descendants(entity) {
+            let slots = running.remove(&e)
+                .into_iter()
+                .chain(self.ecs.scripts.remove(&e))
+                .flatten();
+
+            for mut slot in slots {
+                slot.script.on_destroy(e, self);
+            }
+        }
+
         self.ecs.delete_entity(entity);
     }
 
+    /// ECS - Components
+    pub fn add_component<T: 'static>(&mut self, entity: Entity, component: T) {
+        self.ecs.insert(entity, component);
+    }
+
+    pub fn remove_component<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.ecs.remove::<T>(entity)
+    }
+
     /// ECS - Transform
     pub fn add_transform(&mut self, entity: Entity, transform: Transform) {
         self.ecs.add_transform(entity, transform);
@@ -53,9 +383,22 @@ impl<'a> Engine<'a> {
         self.ecs.transform(entity, transform);
     }
 
+    /// ECS - Hierarchy
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        self.ecs.set_parent(child, parent)
+    }
+
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.ecs.remove_parent(child);
+    }
+
     /// ECS - Mesh
     pub fn add_mesh(&mut self, entity: Entity, mesh: Mesh) {
-        self.ecs.add_mesh(entity, mesh, &self.renderer);
+        self.ecs.add_mesh(entity, mesh);
+    }
+
+    pub fn add_material(&mut self, entity: Entity, material: Material) {
+        self.ecs.add_material(entity, material);
     }
 
     /// ECS - Light
@@ -67,6 +410,102 @@ impl<'a> Engine<'a> {
         self.ecs.edit_light(entity, color, intensity, range);
     }
     
+    /// ECS - Script
+    pub fn add_script(&mut self, entity: Entity, script: impl Script + 'static) {
+        self.ecs.add_script(entity, Box::new(script));
+    }
+
+    /// Скрипты на время обхода вынимаются из ECS, поэтому могут свободно
+    /// менять мир. Добавленные во время обхода запустятся со следующего кадра.
+    /// Удалённые скриптами сущности удаляются после скрипта, сначала вызывается `on_destroy`
+    fn update_scripts(&mut self, dt: f32, fixed: bool) {
+        let mut scripts = std::mem::take(&mut self.ecs.scripts);
+        self.pending_deletions = Some(Vec::new());
+
+        let mut order = scripts.keys().copied().collect::<Vec<_>>();
+        order.sort();
+
+        for entity in order {
+            let Some(mut slots) = scripts.remove(&entity) else {
+                continue;
+            };
+
+            for slot in slots.iter_mut() {
+                if !slot.started {
+                    slot.started = true;
+                    slot.script.start(entity, self);
+
+                    if self.is_pending_deletion(entity) {
+                        break;
+                    }
+                }
+
+                if fixed {
+                    slot.script.fixed_update(entity, self, dt);
+                } else {
+                    slot.script.update(entity, self, dt);
+                }
+
+                if self.is_pending_deletion(entity) {
+                    break;
+                }
+            }
+
+            scripts.insert(entity, slots);
+            self.apply_pending_deletions(&mut scripts);
+        }
+
+        self.pending_deletions = None;
+
+        for (entity, mut slots) in scripts {
+            if self.ecs.is_alive(entity) {
+                if let Some(added) = self.ecs.scripts.remove(&entity) {
+                    slots.extend(added);
+                }
+
+                self.ecs.scripts.insert(entity, slots);
+            } else {
+                // Сущность удалена в обход Engine::delete_entity
+                for slot in &mut slots {
+                    slot.script.on_destroy(entity, self);
+                }
+            }
+        }
+    }
+
+    /// Удалена ли сущность сама или вместе с кем-то из предков
+    fn is_pending_deletion(&self, entity: Entity) -> bool {
+        self.pending_deletions
+            .as_ref()
+            .is_some_and(|pending| pending.iter().any(|p| self.ecs.is_ancestor(*p, entity)))
+    }
+
+    /// `on_destroy` может удалять другие сущности, они удаляются в том же проходе
+    fn apply_pending_deletions(&mut self, scripts: &mut HashMap<Entity, Vec<ScriptSlot>>) {
+        loop {
+            let pending = self.pending_deletions.as_mut().map(std::mem::take).unwrap_or_default();
+            if pending.is_empty() {
+                break;
+            }
+
+            for entity in pending {
+                if self.ecs.is_alive(entity) {
+                    self.destroy_entity(entity, scripts);
+                }
+            }
+        }
+    }
+
+    fn destroy_scripts(&mut self) {
+        let scripts = std::mem::take(&mut self.ecs.scripts);
+
+        for (entity, slots) in scripts {
+            for mut slot in slots {
+                slot.script.on_destroy(entity, self);
+            }
+        }
+    }
+
     /// Renderer
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         self.renderer.render(&mut self.ecs)
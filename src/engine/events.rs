@@ -0,0 +1,180 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use crate::engine::ecs::*;
+
+/// Размер окна изменился
+#[derive(Clone, Copy, Debug)]
+pub struct WindowResized {
+    pub width: u32,
+    pub height: u32
+}
+
+/// Окно получило или потеряло фокус
+#[derive(Clone, Copy, Debug)]
+pub struct WindowFocused(pub bool);
+
+#[derive(Clone, Copy, Debug)]
+pub struct EntitySpawned(pub Entity);
+
+/// Отправляется для каждой удалённой сущности, включая потомков
+#[derive(Clone, Copy, Debug)]
+pub struct EntityDespawned(pub Entity);
+
+/// Столкновение двух сущностей. Отправляется физикой или игровым кодом
+#[derive(Clone, Copy, Debug)]
+pub struct Collision {
+    pub a: Entity,
+    pub b: Entity
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneChanged {
+    pub previous: Option<String>,
+    pub current: String
+}
+
+/// Канал событий одного типа с двойной буферизацией: событие видно
+/// в кадре отправки и в следующем, затем удаляется
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    /// Номер первого события в previous
+    start: usize,
+    /// Всего отправлено событий
+    count: usize
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+            count: 0
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+        self.count += 1;
+    }
+
+    /// Смена кадра: события прошлого кадра удаляются
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Все события, доступные в этом кадре
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    /// Только непрочитанные этим читателем события
+    pub fn read<'a>(&'a self, reader: &mut EventReader<T>) -> impl Iterator<Item = &'a T> + use<'a, T> {
+        let skip = reader.next.saturating_sub(self.start);
+        reader.next = self.count;
+        self.iter().skip(skip)
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.start = self.count;
+        self.previous.clear();
+        self.current.clear();
+    }
+}
+
+/// Позиция читателя в канале. Каждая система или скрипт хранит свой
+pub struct EventReader<T> {
+    next: usize,
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        Self { next: 0, _marker: PhantomData }
+    }
+}
+
+trait AnyEvents {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyEvents for Events<T> {
+    fn update(&mut self) {
+        Events::update(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Набор каналов по типу события. Канал создаётся при первой отправке
+#[derive(Default)]
+pub struct EventBus {
+    channels: HashMap<TypeId, Box<dyn AnyEvents>>
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn channel<T: 'static>(&self) -> Option<&Events<T>> {
+        self.channels
+            .get(&TypeId::of::<T>())
+            .and_then(|c| c.as_any().downcast_ref())
+    }
+
+    pub fn channel_mut<T: 'static>(&mut self) -> &mut Events<T> {
+        self.channels
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Events::<T>::default()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    pub fn send<T: 'static>(&mut self, event: T) {
+        self.channel_mut().send(event);
+    }
+
+    pub fn iter<T: 'static>(&self) -> impl Iterator<Item = &T> {
+        self.channel().into_iter().flat_map(Events::iter)
+    }
+
+    pub fn read<'a, T: 'static>(&'a self, reader: &mut EventReader<T>) -> impl Iterator<Item = &'a T> + use<'a, T> {
+        self.channel().map(|c| c.read(reader)).into_iter().flatten()
+    }
+
+    /// Вызывается движком в начале каждого кадра
+    pub fn update(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.update();
+        }
+    }
+}
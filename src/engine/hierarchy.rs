@@ -0,0 +1,144 @@
+use crate::engine::ecs::*;
+use crate::engine::render::transform::*;
+
+/// Родитель сущности
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parent(pub Entity);
+
+/// Дети сущности
+#[derive(Clone, Debug, Default)]
+pub struct Children(pub Vec<Entity>);
+
+impl ECS {
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.get::<Parent>(entity).map(|p| p.0)
+    }
+
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.get::<Children>(entity).map(|c| c.0.as_slice()).unwrap_or_default()
+    }
+
+    /// Привязка к родителю. Возвращает false, если привязка создала бы цикл
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        if !self.is_alive(child) || !self.is_alive(parent) || self.is_ancestor(child, parent) {
+            return false;
+        }
+
+        self.remove_parent(child);
+        self.insert(child, Parent(parent));
+
+        match self.get_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => self.insert(parent, Children(vec![child]))
+        }
+
+        true
+    }
+
+    pub fn remove_parent(&mut self, child: Entity) {
+        if let Some(Parent(parent)) = self.remove::<Parent>(child)
+            && let Some(children) = self.get_mut::<Children>(parent) {
+            children.0.retain(|c| *c != child);
+        }
+    }
+
+    /// Является ли `ancestor` предком `entity` (или ею самой)
+    pub fn is_ancestor(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut current = Some(entity);
+
+        while let Some(e) = current {
+            if e == ancestor {
+                return true;
+            }
+
+            current = self.parent(e);
+        }
+
+        false
+    }
+
+    /// Сущность и все её потомки, родители раньше детей
+    pub fn descendants(&self, entity: Entity) -> Vec<Entity> {
+        let mut result = vec![entity];
+        let mut i = 0;
+
+        while i < result.len() {
+            result.extend_from_slice(self.children(result[i]));
+            i += 1;
+        }
+
+        result
+    }
+
+    /// Пересчёт мировых позиционирований от корней к листьям. Сущность без
+    /// `Transform` в иерархии считается тождественной: её дети получают
+    /// позиционирование от её предков, сама она `GlobalTransform` не получает
+    pub fn propagate_transforms(&mut self) {
+        let mut roots = self.query::<Transform>()
+            .filter(|(e, _)| !self.has::<Parent>(*e))
+            .map(|(e, t)| (e, t.clone()))
+            .collect::<Vec<_>>();
+
+        roots.extend(
+            self.query::<Children>()
+                .filter(|(e, _)| !self.has::<Parent>(*e) && !self.has::<Transform>(*e))
+                .map(|(e, _)| (e, Transform::default()))
+        );
+
+        let mut stack = Vec::new();
+
+        for (root, transform) in roots {
+            stack.push((root, transform));
+
+            while let Some((entity, world)) = stack.pop() {
+                for child in self.children(entity).to_vec() {
+                    let local = self.get_transform(&child).cloned().unwrap_or_default();
+                    stack.push((child, world.mul_transform(&local)));
+                }
+
+                if !self.has::<Transform>(entity) {
+                    continue;
+                }
+
+                // Без перезаписи неизменившихся, чтобы не отмечать их изменёнными
+                let global = GlobalTransform(world);
+                if self.get::<GlobalTransform>(entity) != Some(&global) {
+                    self.components.insert(entity, global);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::core::primitives::*;
+
+    fn at(x: f32) -> Transform {
+        Transform::new(Vec3::new(x, 0.0, 0.0), Quat::IDENTITY, Vec3::IDENTITY)
+    }
+
+    #[test]
+    fn parents_without_transform_are_identity() {
+        let mut ecs = ECS::new();
+        let group = ecs.create_entity();
+        let middle = ecs.create_entity();
+        let leaf = ecs.create_entity();
+
+        ecs.add_transform(middle, at(1.0));
+        ecs.set_parent(middle, group);
+        ecs.set_parent(leaf, middle);
+
+        let child = ecs.create_entity();
+        ecs.add_transform(child, at(2.0));
+        ecs.set_parent(child, leaf);
+
+        ecs.propagate_transforms();
+
+        assert_eq!(ecs.get::<GlobalTransform>(middle).map(|g| g.0.position), Some(Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(ecs.get::<GlobalTransform>(child).map(|g| g.0.position), Some(Vec3::new(3.0, 0.0, 0.0)));
+        assert!(!ecs.has::<GlobalTransform>(group));
+        assert!(!ecs.has::<GlobalTransform>(leaf));
+    }
+}
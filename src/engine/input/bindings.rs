@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+/// Физическая кнопка
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton)
+}
+
+/// Аналоговый источник мыши
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseAxis {
+    X,
+    Y,
+    Scroll
+}
+
+/// Ось из пары действий и, опционально, движения мыши
+#[derive(Clone, Debug)]
+pub struct Axis {
+    pub positive: String,
+    pub negative: String,
+    pub mouse: Option<MouseAxis>,
+    pub mouse_scale: f32
+}
+
+impl Axis {
+    pub fn new(positive: &str, negative: &str) -> Self {
+        Self {
+            positive: positive.to_string(),
+            negative: negative.to_string(),
+            mouse: None,
+            mouse_scale: 1.0
+        }
+    }
+
+    pub fn with_mouse(mut self, mouse: MouseAxis, scale: f32) -> Self {
+        self.mouse = Some(mouse);
+        self.mouse_scale = scale;
+        self
+    }
+}
+
+/// Соответствие действий кнопкам
+#[derive(Clone, Debug)]
+pub struct InputMap {
+    pub actions: HashMap<String, Vec<Binding>>,
+    pub axes: HashMap<String, Axis>,
+    /// Пары действий, которые не бывают активны одновременно
+    pub shared: Vec<(String, String)>
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+
+        map.bind("move_forward", Binding::Key(KeyCode::KeyW));
+        map.bind("move_backward", Binding::Key(KeyCode::KeyS));
+        map.bind("move_right", Binding::Key(KeyCode::KeyD));
+        map.bind("move_left", Binding::Key(KeyCode::KeyA));
+        map.bind("move_up", Binding::Key(KeyCode::Space));
+        map.bind("move_down", Binding::Key(KeyCode::ShiftLeft));
+
+        map.bind("look_up", Binding::Key(KeyCode::ArrowUp));
+        map.bind("look_down", Binding::Key(KeyCode::ArrowDown));
+        map.bind("look_left", Binding::Key(KeyCode::ArrowLeft));
+        map.bind("look_right", Binding::Key(KeyCode::ArrowRight));
+        map.bind("roll_right", Binding::Key(KeyCode::KeyE));
+        map.bind("roll_left", Binding::Key(KeyCode::KeyQ));
+
+        map.bind("grab_cursor", Binding::Mouse(MouseButton::Left));
+        map.bind("release_cursor", Binding::Key(KeyCode::Escape));
+
+        map.bind("orbit_rotate", Binding::Mouse(MouseButton::Left));
+        map.bind("orbit_pan", Binding::Mouse(MouseButton::Middle));
+        // Захват курсора нужен только свободной камере
+        map.share("grab_cursor", "orbit_rotate");
+
+        map.bind("pause", Binding::Key(KeyCode::KeyP));
+        map.bind("switch_camera", Binding::Key(KeyCode::KeyC));
+
+        map.bind("fov_increase", Binding::Key(KeyCode::Equal));
+        map.bind("fov_decrease", Binding::Key(KeyCode::Minus));
+
+        map.bind("scene_1", Binding::Key(KeyCode::Digit1));
+        map.bind("scene_2", Binding::Key(KeyCode::Digit2));
+        map.bind("scene_3", Binding::Key(KeyCode::Digit3));
+        map.bind("save_scene", Binding::Key(KeyCode::F5));
+        map.bind("quick_save", Binding::Key(KeyCode::F6));
+        map.bind("quick_load", Binding::Key(KeyCode::F9));
+
+        map.add_axis("move_x", Axis::new("move_right", "move_left"));
+        map.add_axis("move_y", Axis::new("move_up", "move_down"));
+        map.add_axis("move_z", Axis::new("move_forward", "move_backward"));
+        map.add_axis("look_x", Axis::new("look_right", "look_left").with_mouse(MouseAxis::X, 1.0));
+        map.add_axis("look_y", Axis::new("look_up", "look_down").with_mouse(MouseAxis::Y, -1.0));
+        map.add_axis("roll", Axis::new("roll_right", "roll_left"));
+        map.add_axis("fov", Axis::new("fov_increase", "fov_decrease").with_mouse(MouseAxis::Scroll, -1.0));
+
+        map
+    }
+}
+
+impl InputMap {
+    pub fn empty() -> Self {
+        Self {
+            actions: HashMap::new(),
+            axes: HashMap::new(),
+            shared: Vec::new()
+        }
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    /// Общая кнопка у этих действий не считается конфликтом
+    pub fn share(&mut self, first: &str, second: &str) {
+        self.shared.push((first.to_string(), second.to_string()));
+    }
+
+    pub fn is_shared(&self, first: &str, second: &str) -> bool {
+        self.shared.iter().any(|(a, b)| (a == first && b == second) || (a == second && b == first))
+    }
+
+    pub fn add_axis(&mut self, name: &str, axis: Axis) {
+        self.axes.insert(name.to_string(), axis);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(Vec::as_slice).unwrap_or_default()
+    }
+}
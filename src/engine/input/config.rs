@@ -0,0 +1,257 @@
+use std::path::Path;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use crate::engine::core::document::*;
+use crate::engine::input::bindings::*;
+
+/// Несколько действий на одной кнопке
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub binding: Binding,
+    pub actions: Vec<String>
+}
+
+impl InputMap {
+    /// Загрузка раскладки. Действия, которых нет в файле, остаются по умолчанию,
+    /// пустое значение снимает все кнопки с действия
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        Self::from_document(&Document::load(path)?)
+    }
+
+    pub fn from_document(document: &Document) -> Result<Self, String> {
+        let mut map = Self::default();
+
+        if let Some(section) = document.section("bindings") {
+            for (action, value) in &section.entries {
+                let bindings = value
+                    .split_whitespace()
+                    .map(|name| parse_binding(name).ok_or_else(|| format!("[bindings] {}: неизвестная кнопка \"{}\"", action, name)))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                map.actions.insert(action.clone(), bindings);
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Загрузка раскладки или создание файла с раскладкой по умолчанию
+    pub fn load_or_create(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            let map = Self::default();
+            if let Err(e) = map.save(path) {
+                eprintln!("Ошибка сохранения управления: {}", e);
+            }
+            return map;
+        }
+
+        match Self::load(path) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("Ошибка загрузки управления, используются настройки по умолчанию: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Кнопки без имени в файл не попадают, о каждой выводится предупреждение
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        for (action, binding) in self.unnamed_bindings() {
+            eprintln!("[bindings] {}: у кнопки {:?} нет имени, она не сохраняется", action, binding);
+        }
+
+        self.to_document().save(path)
+    }
+
+    pub fn to_document(&self) -> Document {
+        let mut section = Section::new("bindings");
+
+        let mut actions = self.actions.keys().collect::<Vec<_>>();
+        actions.sort();
+
+        for action in actions {
+            let names = self.bindings(action)
+                .iter()
+                .filter_map(|b| binding_name(*b))
+                .collect::<Vec<_>>();
+
+            section.set(action, names.join(" "));
+        }
+
+        let mut document = Document::new();
+        document.push(section);
+        document
+    }
+
+    /// Назначенные кнопки, которые нельзя записать в файл
+    pub fn unnamed_bindings(&self) -> Vec<(String, Binding)> {
+        let mut unnamed = self.actions
+            .iter()
+            .flat_map(|(action, bindings)| bindings.iter().map(move |b| (action.clone(), *b)))
+            .filter(|(_, b)| binding_name(*b).is_none())
+            .collect::<Vec<_>>();
+
+        unnamed.sort_by(|a, b| a.0.cmp(&b.0));
+        unnamed
+    }
+
+    /// Кнопки, назначенные сразу нескольким действиям
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts: Vec<Conflict> = Vec::new();
+
+        let mut actions = self.actions.iter().collect::<Vec<_>>();
+        actions.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (action, bindings) in actions {
+            for binding in bindings {
+                match conflicts.iter_mut().find(|c| c.binding == *binding) {
+                    Some(conflict) => conflict.actions.push(action.clone()),
+                    None => conflicts.push(Conflict { binding: *binding, actions: vec![action.clone()] })
+                }
+            }
+        }
+
+        conflicts.retain(|c| c.actions.iter().enumerate().any(|(i, first)| {
+            c.actions[i + 1..].iter().any(|second| !self.is_shared(first, second))
+        }));
+        conflicts
+    }
+}
+
+/// Имена кнопок
+pub fn parse_binding(name: &str) -> Option<Binding> {
+    match name {
+        "MouseLeft" => Some(Binding::Mouse(MouseButton::Left)),
+        "MouseRight" => Some(Binding::Mouse(MouseButton::Right)),
+        "MouseMiddle" => Some(Binding::Mouse(MouseButton::Middle)),
+        "MouseBack" => Some(Binding::Mouse(MouseButton::Back)),
+        "MouseForward" => Some(Binding::Mouse(MouseButton::Forward)),
+        _ => match name.strip_prefix("Mouse") {
+            Some(n) => n.parse().ok().map(|n| Binding::Mouse(MouseButton::Other(n))),
+            None => parse_key(name).map(Binding::Key)
+        }
+    }
+}
+
+pub fn binding_name(binding: Binding) -> Option<String> {
+    match binding {
+        Binding::Mouse(MouseButton::Left) => Some("MouseLeft".to_string()),
+        Binding::Mouse(MouseButton::Right) => Some("MouseRight".to_string()),
+        Binding::Mouse(MouseButton::Middle) => Some("MouseMiddle".to_string()),
+        Binding::Mouse(MouseButton::Back) => Some("MouseBack".to_string()),
+        Binding::Mouse(MouseButton::Forward) => Some("MouseForward".to_string()),
+        Binding::Mouse(MouseButton::Other(n)) => Some(format!("Mouse{}", n)),
+        Binding::Key(key) => key_name(key).map(str::to_string)
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        pub fn parse_key(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None
+            }
+        }
+
+        pub fn key_name(key: KeyCode) -> Option<&'static str> {
+            match key {
+                $(KeyCode::$key => Some(stringify!($key)),)*
+                _ => None
+            }
+        }
+    };
+}
+
+key_names! {
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+    KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    Backquote, Backslash, BracketLeft, BracketRight, Comma, Equal, IntlBackslash,
+    Minus, Period, Quote, Semicolon, Slash,
+    AltLeft, AltRight, Backspace, CapsLock, ControlLeft, ControlRight, Enter,
+    ShiftLeft, ShiftRight, Space, Tab, Escape,
+    Delete, End, Home, Insert, PageDown, PageUp,
+    ArrowDown, ArrowLeft, ArrowRight, ArrowUp,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadDecimal, NumpadDivide, NumpadEnter, NumpadMultiply, NumpadSubtract,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<InputMap, String> {
+        InputMap::from_document(&Document::parse(text).unwrap())
+    }
+
+    #[test]
+    fn file_overrides_only_listed_actions() {
+        let map = parse("[bindings]\nmove_forward = KeyI ArrowUp\npause =\n").unwrap();
+
+        assert_eq!(map.bindings("move_forward"), &[Binding::Key(KeyCode::KeyI), Binding::Key(KeyCode::ArrowUp)]);
+        assert!(map.bindings("pause").is_empty());
+        assert_eq!(map.bindings("move_backward"), &[Binding::Key(KeyCode::KeyS)]);
+    }
+
+    #[test]
+    fn unknown_button_is_an_error() {
+        let error = parse("[bindings]\nmove_forward = KeyW Banana\n").unwrap_err();
+        assert!(error.contains("Banana"), "{}", error);
+    }
+
+    #[test]
+    fn binding_names_round_trip() {
+        for binding in [
+            Binding::Key(KeyCode::KeyW),
+            Binding::Key(KeyCode::F12),
+            Binding::Mouse(MouseButton::Left),
+            Binding::Mouse(MouseButton::Other(7))
+        ] {
+            assert_eq!(parse_binding(&binding_name(binding).unwrap()), Some(binding));
+        }
+    }
+
+    #[test]
+    fn default_map_round_trips_through_text() {
+        let map = InputMap::default();
+        assert!(map.unnamed_bindings().is_empty());
+
+        let text = map.to_document().to_string();
+        let loaded = InputMap::from_document(&Document::parse(&text).unwrap()).unwrap();
+
+        for action in map.actions.keys() {
+            assert_eq!(loaded.bindings(action), map.bindings(action), "{}", action);
+        }
+    }
+
+    #[test]
+    fn unnamed_bindings_are_reported() {
+        let mut map = InputMap::default();
+        map.bind("pause", Binding::Key(KeyCode::MediaPlayPause));
+
+        assert_eq!(map.unnamed_bindings(), [("pause".to_string(), Binding::Key(KeyCode::MediaPlayPause))]);
+        assert_eq!(map.to_document().section("bindings").unwrap().get("pause"), Some("KeyP"));
+    }
+
+    #[test]
+    fn default_map_has_no_conflicts() {
+        assert!(InputMap::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn shared_button_is_a_conflict_unless_declared() {
+        let mut map = parse("[bindings]\npause = KeyW\n").unwrap();
+
+        let conflicts = map.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].binding, Binding::Key(KeyCode::KeyW));
+        assert_eq!(conflicts[0].actions, ["move_forward", "pause"]);
+
+        map.share("pause", "move_forward");
+        assert!(map.conflicts().is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use winit::event::*;
+use winit::keyboard::{KeyCode, PhysicalKey};
+use crate::engine::input::bindings::*;
+
+/// Состояние ввода за кадр
+#[derive(Default)]
+pub struct Input {
+    pub map: InputMap,
+    down: HashSet<Binding>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+    mouse_delta: (f32, f32),
+    scroll: f32,
+    cursor: Option<(f32, f32)>,
+    unfocused: bool
+}
+
+impl Input {
+    pub fn new(map: InputMap) -> Self {
+        Self { map, ..Default::default() }
+    }
+
+    /// События
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(code) = event.physical_key && !event.repeat {
+                    self.set(Binding::Key(code), event.state);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.set(Binding::Mouse(*button), *state);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 100.0
+                };
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+            }
+            WindowEvent::Focused(focused) => {
+                self.unfocused = !focused;
+
+                if !focused {
+                    self.release_all();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.mouse_delta.0 += delta.0 as f32;
+            self.mouse_delta.1 += delta.1 as f32;
+        }
+    }
+
+    /// Сброс однокадровых состояний. Вызывается в конце кадра
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll = 0.0;
+    }
+
+    pub fn release_all(&mut self) {
+        self.released.extend(self.down.drain());
+    }
+
+    fn set(&mut self, binding: Binding, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.down.insert(binding) {
+                    self.pressed.insert(binding);
+                }
+            }
+            ElementState::Released => {
+                if self.down.remove(&binding) {
+                    self.released.insert(binding);
+                }
+            }
+        }
+    }
+
+    /// Кнопки
+    pub fn is_down(&self, binding: Binding) -> bool {
+        self.down.contains(&binding)
+    }
+
+    pub fn just_pressed(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    pub fn just_released(&self, binding: Binding) -> bool {
+        self.released.contains(&binding)
+    }
+
+    pub fn key_down(&self, key: KeyCode) -> bool {
+        self.is_down(Binding::Key(key))
+    }
+
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.just_pressed(Binding::Key(key))
+    }
+
+    pub fn key_released(&self, key: KeyCode) -> bool {
+        self.just_released(Binding::Key(key))
+    }
+
+    pub fn mouse_down(&self, button: MouseButton) -> bool {
+        self.is_down(Binding::Mouse(button))
+    }
+
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed(Binding::Mouse(button))
+    }
+
+    pub fn mouse_released(&self, button: MouseButton) -> bool {
+        self.just_released(Binding::Mouse(button))
+    }
+
+    /// Мышь
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    pub fn cursor(&self) -> Option<(f32, f32)> {
+        self.cursor
+    }
+
+    pub fn focused(&self) -> bool {
+        !self.unfocused
+    }
+
+    /// Действия
+    pub fn action_down(&self, action: &str) -> bool {
+        self.map.bindings(action).iter().any(|b| self.is_down(*b))
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.map.bindings(action).iter().any(|b| self.just_pressed(*b))
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.map.bindings(action).iter().any(|b| self.just_released(*b))
+    }
+
+    /// Значение оси: кнопки дают -1..1, мышь добавляет своё смещение за кадр
+    pub fn axis(&self, name: &str) -> f32 {
+        self.axis_buttons(name) + self.axis_mouse(name)
+    }
+
+    /// Значение оси только по удерживаемым кнопкам
+    pub fn axis_buttons(&self, name: &str) -> f32 {
+        let Some(axis) = self.map.axes.get(name) else {
+            return 0.0;
+        };
+
+        let mut value = 0.0;
+
+        if self.action_down(&axis.positive) {
+            value += 1.0;
+        }
+
+        if self.action_down(&axis.negative) {
+            value -= 1.0;
+        }
+
+        value
+    }
+
+    /// Значение оси только по мыши
+    pub fn axis_mouse(&self, name: &str) -> f32 {
+        self.map.axes
+            .get(name)
+            .map(|axis| self.mouse_axis(axis) * axis.mouse_scale)
+            .unwrap_or(0.0)
+    }
+
+    /// Значение оси только по кнопкам, нажатым в этом кадре
+    pub fn axis_pressed(&self, name: &str) -> f32 {
+        let Some(axis) = self.map.axes.get(name) else {
+            return 0.0;
+        };
+
+        let mut value = 0.0;
+
+        if self.action_pressed(&axis.positive) {
+            value += 1.0;
+        }
+
+        if self.action_pressed(&axis.negative) {
+            value -= 1.0;
+        }
+
+        value
+    }
+
+    fn mouse_axis(&self, axis: &Axis) -> f32 {
+        match axis.mouse {
+            Some(MouseAxis::X) => self.mouse_delta.0,
+            Some(MouseAxis::Y) => self.mouse_delta.1,
+            Some(MouseAxis::Scroll) => self.scroll,
+            None => 0.0
+        }
+    }
+}
@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod input;
+pub mod bindings;
+pub mod config;
@@ -1,5 +1,17 @@
 pub mod render;
 pub mod ecs;
+#[allow(clippy::module_inception)]
 pub mod engine;
 pub mod core;
 pub mod objects;
+pub mod prefab;
+pub mod scene;
+pub mod script;
+pub mod input;
+pub mod controllers;
+pub mod time;
+pub mod components;
+pub mod hierarchy;
+pub mod schedule;
+pub mod resources;
+pub mod events;
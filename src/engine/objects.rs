@@ -1,70 +1,80 @@
-use crate::engine::render::transform::*;
+use crate::engine::core::primitives::*;
 use crate::engine::render::mesh::*;
 use crate::engine::render::renderable::*;
 use crate::engine::ecs::*;
 use crate::engine::engine::*;
+use crate::engine::prefab::*;
 
 /// Куб
-pub fn cube(engine: &mut Engine) -> Entity {
-    let entity = engine.create_entity();
-
-    let transform = Transform::default();
-    engine.add_transform(entity, transform);
-
-    let mesh = Mesh::cube();
-    engine.add_mesh(entity, mesh);
+pub fn cube_prefab() -> Prefab {
+    Prefab::new("cube").with_mesh(Mesh::cube())
+}
 
-    entity
+pub fn cube(engine: &mut Engine) -> Entity {
+    engine.spawn(&cube_prefab())
 }
 
 /// Конус
-pub fn cone(engine: &mut Engine, segments: u16) -> Entity {
-    let entity = engine.create_entity();
-
-    let transform = Transform::default();
-    engine.add_transform(entity, transform);
-
-    let mesh = Mesh::cone(segments);
-    engine.add_mesh(entity, mesh);
+pub fn cone_prefab(segments: u16) -> Prefab {
+    Prefab::new("cone").with_mesh(Mesh::cone(segments))
+}
 
-    entity
+pub fn cone(engine: &mut Engine, segments: u16) -> Entity {
+    engine.spawn(&cone_prefab(segments))
 }
 
 /// Цилиндр
+pub fn cylinder_prefab(segments: u16) -> Prefab {
+    Prefab::new("cylinder").with_mesh(Mesh::cylinder(segments))
+}
+
 pub fn cylinder(engine: &mut Engine, segments: u16) -> Entity {
-    let entity = engine.create_entity();
-    
-    let transform = Transform::default();
-    engine.add_transform(entity, transform);
-    
-    let mesh = Mesh::cylinder(segments);
-    engine.add_mesh(entity, mesh);
-    
-    entity
+    engine.spawn(&cylinder_prefab(segments))
 }
 
 /// Шар
+pub fn sphere_prefab(segments: u16) -> Prefab {
+    Prefab::new("sphere").with_mesh(Mesh::sphere(segments))
+}
+
 pub fn sphere(engine: &mut Engine, segments: u16) -> Entity {
-    let entity = engine.create_entity();
-    
-    let transform = Transform::default();
-    engine.add_transform(entity, transform);
-    
-    let mesh = Mesh::sphere(segments);
-    engine.add_mesh(entity, mesh);
-    
-    entity
+    engine.spawn(&sphere_prefab(segments))
 }
 
 /// Освещение
+pub fn light_prefab() -> Prefab {
+    Prefab::new("light").with_light(Light::default())
+}
+
 pub fn light(engine: &mut Engine) -> Entity {
-    let entity = engine.create_entity();
+    engine.spawn(&light_prefab())
+}
+
+/// Направленное освещение, светит вдоль оси -Z сущности
+pub fn directional_light_prefab() -> Prefab {
+    Prefab::new("directional_light").with_light(Light::directional(Vec3::IDENTITY, 1.0))
+}
+
+pub fn directional_light(engine: &mut Engine) -> Entity {
+    engine.spawn(&directional_light_prefab())
+}
+
+/// Прожектор, светит вдоль оси -Z сущности
+pub fn spot_light_prefab() -> Prefab {
+    Prefab::new("spot_light").with_light(Light::spot(Vec3::IDENTITY, 10.0, 50.0, 20.0, 30.0))
+}
+
+pub fn spot_light(engine: &mut Engine) -> Entity {
+    engine.spawn(&spot_light_prefab())
+}
 
-    let transform = Transform::default();
-    engine.add_transform(entity, transform);
-    
-    let light = Light::default();
-    engine.add_light(entity, light);
-    
-    entity
+/// Регистрация встроенных шаблонов под их именами
+pub fn register_prefabs(engine: &mut Engine) {
+    engine.register_prefab(cube_prefab());
+    engine.register_prefab(cone_prefab(32));
+    engine.register_prefab(cylinder_prefab(32));
+    engine.register_prefab(sphere_prefab(32));
+    engine.register_prefab(light_prefab());
+    engine.register_prefab(directional_light_prefab());
+    engine.register_prefab(spot_light_prefab());
 }
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use crate::engine::core::document::*;
+use crate::engine::core::primitives::*;
+use crate::engine::ecs::*;
+use crate::engine::engine::*;
+use crate::engine::render::material::*;
+use crate::engine::render::mesh::*;
+use crate::engine::render::renderable::*;
+use crate::engine::render::transform::*;
+use crate::engine::scene::file::*;
+
+/// Добавление произвольного компонента экземпляру
+type ComponentFn = Rc<dyn Fn(&mut Engine, Entity)>;
+
+/// Шаблон сущности: компоненты и дочерние шаблоны. Описывается в коде
+/// или файле и создаётся сколько угодно раз через `Engine::spawn`
+#[derive(Clone, Default)]
+pub struct Prefab {
+    pub name: String,
+    pub transform: Transform,
+    pub mesh: Option<Mesh>,
+    pub material: Option<Material>,
+    pub light: Option<Light>,
+    pub children: Vec<Prefab>,
+    components: Vec<ComponentFn>
+}
+
+impl Prefab {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Default::default() }
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: Mesh) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.light = Some(light);
+        self
+    }
+
+    /// Произвольный компонент, копия которого достаётся каждому экземпляру
+    pub fn with_component<T: Clone + 'static>(mut self, component: T) -> Self {
+        self.components.push(Rc::new(move |engine, entity| engine.add_component(entity, component.clone())));
+        self
+    }
+
+    /// Позиционирование ребёнка задаётся относительно родителя
+    pub fn with_child(mut self, child: Prefab) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Создание сущности с потомками. Положение, поворот и масштаб меняются
+    /// у корня, цвет - у всей иерархии
+    pub fn instantiate(&self, engine: &mut Engine, overrides: &Overrides) -> Entity {
+        let mut transform = self.transform.clone();
+        if let Some(position) = overrides.position { transform.position = position; }
+        if let Some(rotation) = overrides.rotation { transform.rotation = rotation; }
+        if let Some(scale) = overrides.scale { transform.scale = scale; }
+
+        let entity = engine.create_entity();
+        engine.add_transform(entity, transform);
+
+        if let Some(mesh) = &self.mesh {
+            engine.add_mesh(entity, mesh.clone());
+        }
+
+        // Цвет сетки без материала задаёт материал по умолчанию с этим цветом
+        let material = match (&self.material, overrides.color) {
+            (Some(material), Some(color)) => Some(Material { base_color: color, ..material.clone() }),
+            (None, Some(color)) if self.mesh.is_some() => Some(Material::new(color)),
+            (material, _) => material.clone()
+        };
+
+        if let Some(material) = material {
+            engine.add_material(entity, material);
+        }
+
+        if let Some(light) = &self.light {
+            let mut light = *light;
+            if let Some(color) = overrides.color { light.color = color; }
+            engine.add_light(entity, light);
+        }
+
+        for component in &self.components {
+            component(engine, entity);
+        }
+
+        let child_overrides = Overrides { color: overrides.color, ..Overrides::default() };
+        for child in &self.children {
+            let child = child.instantiate(engine, &child_overrides);
+            engine.set_parent(child, entity);
+        }
+
+        entity
+    }
+
+    /// Шаблон из файла. Формат как у файла сцены, корнем становится
+    /// единственная сущность без родителя:
+    ///
+    /// ```text
+    /// [prefab]
+    /// name = lamp_post
+    ///
+    /// [entity]
+    /// id = lamp
+    ///
+    /// [entity]
+    /// parent = lamp
+    /// scale = 0.1 3 0.1
+    /// mesh = cylinder 16
+    ///
+    /// [entity]
+    /// parent = lamp
+    /// position = 0 1.8 0
+    /// light = point
+    /// ```
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut prefab = Self::from_document(&Document::load(path)?)?;
+
+        if prefab.name.is_empty() {
+            prefab.name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        }
+
+        Ok(prefab)
+    }
+
+    pub fn from_document(document: &Document) -> Result<Self, String> {
+        let mut nodes = Vec::new();
+        let mut ids = HashMap::new();
+
+        for section in document.sections("entity") {
+            let desc = read_entity(section)?;
+
+            let EntityContent::Components { transform, mesh, material, light } = desc.content else {
+                return Err(format!("[entity] (строка {}): вложенные шаблоны не поддерживаются", desc.line));
+            };
+
+            let node = Prefab { transform, mesh, material, light, ..Prefab::new("") };
+
+            if let Some(id) = desc.id && ids.insert(id.clone(), nodes.len()).is_some() {
+                return Err(format!("[entity] (строка {}): id \"{}\" уже занят", desc.line, id));
+            }
+
+            nodes.push((node, desc.parent, desc.line));
+        }
+
+        let mut parents = vec![None; nodes.len()];
+        let mut root = None;
+
+        for (i, (_, parent, line)) in nodes.iter().enumerate() {
+            match parent {
+                Some(parent) => {
+                    let index = *ids
+                        .get(parent)
+                        .ok_or_else(|| format!("[entity] (строка {}): нет сущности с id \"{}\"", line, parent))?;
+                    parents[i] = Some(index);
+                }
+                None if root.is_some() => {
+                    return Err(format!("[entity] (строка {}): у шаблона может быть только один корень", line));
+                }
+                None => root = Some(i)
+            }
+        }
+
+        let root = root.ok_or("в шаблоне нет сущности без родителя")?;
+        let mut nodes = nodes.into_iter().map(|(node, _, _)| Some(node)).collect::<Vec<_>>();
+        let mut prefab = attach_children(root, &mut nodes, &parents);
+
+        if nodes.iter().any(Option::is_some) {
+            return Err("циклическая иерархия".to_string());
+        }
+
+        if let Some(section) = document.section("prefab") {
+            warn_unknown_keys(section, &[&["name"]]);
+
+            if let Some(name) = section.get("name") {
+                prefab.name = name.to_string();
+            }
+        }
+
+        Ok(prefab)
+    }
+}
+
+/// Сборка дерева шаблонов от корня. Узлы, не достижимые из корня, остаются в `nodes`
+fn attach_children(index: usize, nodes: &mut [Option<Prefab>], parents: &[Option<usize>]) -> Prefab {
+    let mut node = nodes[index].take().unwrap_or_default();
+
+    for child in 0..parents.len() {
+        if parents[child] == Some(index) {
+            node.children.push(attach_children(child, nodes, parents));
+        }
+    }
+
+    node
+}
+
+/// Изменения шаблона при создании экземпляра
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Overrides {
+    pub position: Option<Vec3>,
+    pub rotation: Option<Quat>,
+    pub scale: Option<Vec3>,
+    /// Цвет источников света и основной цвет материалов корня и всех потомков
+    pub color: Option<Vec3>
+}
+
+impl Overrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn at(mut self, position: Vec3) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn rotated(mut self, rotation: Quat) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    pub fn scaled(mut self, scale: Vec3) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn colored(mut self, color: Vec3) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+
+/// Именованные шаблоны, хранятся в ресурсах ECS
+#[derive(Clone, Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>
+}
+
+impl Prefabs {
+    pub fn register(&mut self, prefab: Prefab) {
+        self.prefabs.insert(prefab.name.clone(), prefab);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.prefabs.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.prefabs.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Prefab, String> {
+        Prefab::from_document(&Document::parse(text)?)
+    }
+
+    #[test]
+    fn lamp_post_file_builds_hierarchy() {
+        let prefab = Prefab::load("assets/prefabs/lamp_post.prefab").unwrap();
+
+        assert_eq!(prefab.name, "lamp_post");
+        assert_eq!(prefab.transform, Transform::default());
+        assert!(prefab.mesh.is_none() && prefab.light.is_none());
+        assert_eq!(prefab.children.len(), 2);
+
+        let post = &prefab.children[0];
+        assert_eq!(post.mesh.as_ref().map(|m| m.kind), Some(MeshKind::Cylinder(16)));
+        assert_eq!(post.material.as_ref().map(|m| m.metallic), Some(1.0));
+
+        let bulb = &prefab.children[1];
+        assert_eq!(bulb.transform.position, Vec3::new(0.0, 1.8, 0.0));
+        assert_eq!(bulb.transform.scale, Vec3::IDENTITY);
+        assert_eq!(bulb.light.map(|l| l.light_type), Some(LIGHT_POINT));
+    }
+
+    #[test]
+    fn nested_children_follow_parent_ids() {
+        let prefab = parse("\
+[prefab]
+name = tree
+
+[entity]
+id = trunk
+mesh = cylinder 8
+
+[entity]
+id = crown
+parent = trunk
+mesh = sphere 8
+
+[entity]
+parent = crown
+light = point
+").unwrap();
+
+        assert_eq!(prefab.name, "tree");
+        assert_eq!(prefab.children.len(), 1);
+        assert_eq!(prefab.children[0].children.len(), 1);
+        assert!(prefab.children[0].children[0].light.is_some());
+    }
+
+    #[test]
+    fn invalid_hierarchies_are_rejected() {
+        assert!(parse("[entity]\nmesh = cube\n\n[entity]\nmesh = cube\n").err().unwrap().contains("один корень"));
+        assert!(parse("[entity]\nid = a\nparent = b\n\n[entity]\nid = b\nparent = a\n").is_err());
+        assert!(parse("[entity]\nid = a\n\n[entity]\nid = a\n").err().unwrap().contains("уже занят"));
+        assert!(parse("[entity]\nparent = nobody\n").err().unwrap().contains("nobody"));
+        assert!(parse("[entity]\nprefab = other\n").err().unwrap().contains("вложенные шаблоны"));
+        assert!(parse("[prefab]\nname = empty\n").is_err());
+    }
+}
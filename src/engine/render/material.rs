@@ -0,0 +1,89 @@
+use crate::engine::core::primitives::*;
+use crate::engine::render::texture::*;
+
+/// Материал поверхности в модели metallic-roughness. Сетки без
+/// материала рисуются материалом по умолчанию: белым и матовым
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub base_color: Vec3,
+    /// Собственное свечение, не зависит от освещения
+    pub emissive: Vec3,
+    /// 0 - зеркальная поверхность, 1 - полностью матовая
+    pub roughness: f32,
+    /// 0 - диэлектрик, 1 - металл
+    pub metallic: f32,
+    /// Пути к текстурам. Цвет из текстуры умножается на `base_color`
+    pub base_color_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    /// Выборка из обеих текстур
+    pub sampler: SamplerOptions
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: Vec3::IDENTITY,
+            emissive: Vec3::ZERO,
+            roughness: 0.5,
+            metallic: 0.0,
+            base_color_texture: None,
+            normal_texture: None,
+            sampler: SamplerOptions::default()
+        }
+    }
+}
+
+impl Material {
+    pub fn new(base_color: Vec3) -> Self {
+        Self { base_color, ..Self::default() }
+    }
+
+    pub fn with_emissive(mut self, emissive: Vec3) -> Self {
+        self.emissive = emissive;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> Self {
+        self.metallic = metallic.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_base_color_texture(mut self, path: &str) -> Self {
+        self.base_color_texture = Some(path.to_string());
+        self
+    }
+
+    pub fn with_normal_texture(mut self, path: &str) -> Self {
+        self.normal_texture = Some(path.to_string());
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerOptions) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn uniforms(&self) -> MaterialUniforms {
+        MaterialUniforms {
+            base_color: self.base_color,
+            roughness: self.roughness,
+            emissive: self.emissive,
+            metallic: self.metallic
+        }
+    }
+}
+
+/// Униформа материала. Раскладка совпадает со структурой `Material` в шейдере
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniforms {
+    pub base_color: Vec3,
+    pub roughness: f32,
+    pub emissive: Vec3,
+    pub metallic: f32
+}
@@ -1,44 +1,68 @@
 use crate::engine::core::primitives::*;
 
-/// Вершина
+/// Вершина. Раскладка совпадает с `VertexInput` в шейдере
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: Vec3,
-    pub normal: Vec3
+    pub normal: Vec3,
+    /// Текстурные координаты, v направлена вниз
+    pub uv: [f32; 2],
+    /// Касательная вдоль u и знак битангенса в w. Заполняется `Mesh::generate_tangents`,
+    /// а у сетки совсем без касательных - рендерером перед загрузкой на видеокарту
+    pub tangent: [f32; 4]
 }
 
 impl Vertex {
-    pub fn new(position: Vec3, normal: Vec3) -> Self {
-        Self { position, normal }
+    pub fn new(position: Vec3, normal: Vec3, uv: [f32; 2]) -> Self {
+        Self { position, normal, uv, tangent: [0.0; 4] }
     }
 }
 
-/// Полигональная сетка
-#[derive(Clone)]
-pub struct Mesh {
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u16>
+/// Происхождение сетки. Встроенные сетки можно пересоздать по параметрам
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MeshKind {
+    #[default]
+    Custom,
+    Cube,
+    Cone(u16),
+    Cylinder(u16),
+    Sphere(u16)
 }
 
-impl Default for Mesh {
-    fn default() -> Self {
-        Self {
-            vertices: Vec::new(),
-            indices: Vec::new()
+impl MeshKind {
+    pub fn build(&self) -> Option<Mesh> {
+        match *self {
+            MeshKind::Custom => None,
+            MeshKind::Cube => Some(Mesh::cube()),
+            MeshKind::Cone(segments) => Some(Mesh::cone(segments)),
+            MeshKind::Cylinder(segments) => Some(Mesh::cylinder(segments)),
+            MeshKind::Sphere(segments) => Some(Mesh::sphere(segments))
         }
     }
 }
 
+/// Полигональная сетка
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+    pub kind: MeshKind
+}
+
 impl Mesh {
     pub fn new(vertices: Vec<Vertex>, indices: Vec<u16>) -> Self {
-        Self { vertices, indices }
+        Self { vertices, indices, kind: MeshKind::Custom }
     }
 
     pub fn index_count(&self) -> u32 {
         self.indices.len() as u32
     }
 
+    pub fn has_tangents(&self) -> bool {
+        self.vertices.iter().any(|vertex| vertex.tangent != [0.0; 4])
+    }
+
     pub fn generate_normals(&mut self) {
         for vertex in &mut self.vertices {
             vertex.normal = Vec3::ZERO;
@@ -67,38 +91,100 @@ impl Mesh {
         }
     }
 
+    /// Касательные по текстурным координатам в духе MikkTSpace: вклады
+    /// треугольников копятся в вершинах, затем касательная ортогонализуется
+    /// к нормали. Считается без округления `Vec3`, иначе на мелких
+    /// треугольниках вклады обнуляются
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![[0.0f32; 3]; self.vertices.len()];
+        let mut bitangents = vec![[0.0f32; 3]; self.vertices.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let [i0, i1, i2] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let (v0, v1, v2) = (&self.vertices[i0], &self.vertices[i1], &self.vertices[i2]);
+
+            let edge1 = [v1.position.x - v0.position.x, v1.position.y - v0.position.y, v1.position.z - v0.position.z];
+            let edge2 = [v2.position.x - v0.position.x, v2.position.y - v0.position.y, v2.position.z - v0.position.z];
+            let (du1, dv1) = (v1.uv[0] - v0.uv[0], v1.uv[1] - v0.uv[1]);
+            let (du2, dv2) = (v2.uv[0] - v0.uv[0], v2.uv[1] - v0.uv[1]);
+
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+
+            let r = 1.0 / det;
+            let tangent: [f32; 3] = std::array::from_fn(|k| (edge1[k] * dv2 - edge2[k] * dv1) * r);
+            let bitangent: [f32; 3] = std::array::from_fn(|k| (edge2[k] * du1 - edge1[k] * du2) * r);
+
+            for i in [i0, i1, i2] {
+                for k in 0..3 {
+                    tangents[i][k] += tangent[k];
+                    bitangents[i][k] += bitangent[k];
+                }
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            let normal = vertex.normal;
+            let [tx, ty, tz] = tangents[i];
+            let along_normal = tx * normal.x + ty * normal.y + tz * normal.z;
+            let mut tangent = Vec3 {
+                x: tx - normal.x * along_normal,
+                y: ty - normal.y * along_normal,
+                z: tz - normal.z * along_normal
+            };
+
+            // Вырожденные развёртки, например полюса шара
+            if tangent.length() < 1e-6 {
+                let axis = if normal.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+                tangent = axis.cross(normal).cross(normal);
+            }
+
+            let tangent = tangent.normalize();
+            let [bx, by, bz] = bitangents[i];
+            let handedness = if normal.cross(tangent).dot(Vec3 { x: bx, y: by, z: bz }) < 0.0 { -1.0 } else { 1.0 };
+
+            vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+        }
+    }
+
+    /// Куб со стороной 1. У каждой грани свои вершины, текстура
+    /// натягивается на грань целиком
     pub fn cube() -> Mesh {
-        let positions = vec![
-            Vec3::new(-0.5, -0.5,  0.5), // 0
-            Vec3::new( 0.5, -0.5,  0.5), // 1
-            Vec3::new( 0.5,  0.5,  0.5), // 2
-            Vec3::new(-0.5,  0.5,  0.5), // 3
-            Vec3::new(-0.5, -0.5, -0.5), // 4
-            Vec3::new( 0.5, -0.5, -0.5), // 5
-            Vec3::new( 0.5,  0.5, -0.5), // 6
-            Vec3::new(-0.5,  0.5, -0.5)  // 7
+        // Нормаль грани и оси текстуры: u x v = нормаль
+        let faces = [
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (-Vec3::Z, -Vec3::X, Vec3::Y),
+            (Vec3::X, -Vec3::Z, Vec3::Y),
+            (-Vec3::X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::X, -Vec3::Z),
+            (-Vec3::Y, Vec3::X, Vec3::Z)
         ];
 
-        let vertices = positions
-            .into_iter()
-            .map(|pos| Vertex::new(pos, Vec3::ZERO))
-            .collect::<Vec<_>>();
-
-        let indices = vec![
-            0, 1, 2,  2, 3, 0,
-            5, 4, 7,  7, 6, 5,
-            4, 0, 3,  3, 7, 4,
-            1, 5, 6,  6, 2, 1,
-            3, 2, 6,  6, 7, 3,
-            4, 5, 1,  1, 0, 4
-        ];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for (normal, u, v) in faces {
+            let base = vertices.len() as u16;
+
+            for (s, t) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let position = (normal + u * (s * 2.0 - 1.0) + v * (t * 2.0 - 1.0)) * 0.5;
+                vertices.push(Vertex::new(position, normal, [s, 1.0 - t]));
+            }
+
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
 
         let mut mesh = Mesh::new(vertices, indices);
-        mesh.generate_normals();
+        mesh.generate_tangents();
+        mesh.kind = MeshKind::Cube;
 
         mesh
     }
 
+    /// Конус радиусом 0.5 и высотой 1. Боковая поверхность развёрнута
+    /// по u вокруг оси, основание - круг в центре текстуры
     pub fn cone(segments: u16) -> Mesh {
         let radius = 0.5;
         let height = 1.0;
@@ -106,121 +192,68 @@ impl Mesh {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
 
-        let tip = Vec3::new(0.0, height / 2.0, 0.0);
-        vertices.push(Vertex::new(tip, Vec3::ZERO));
+        // Бок: вершина конуса своя для каждого сегмента, чтобы нормали не усреднялись
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let theta = u * std::f32::consts::TAU;
+            let tip_theta = (i as f32 + 0.5) / segments as f32 * std::f32::consts::TAU;
 
-        let base_center = Vec3::new(0.0, -height / 2.0, 0.0);
-        vertices.push(Vertex::new(base_center, Vec3::ZERO));
+            let ring = Vec3::new(radius * theta.cos(), -height / 2.0, radius * theta.sin());
+            let normal = Vec3::new(height * theta.cos(), radius, height * theta.sin()).normalize();
+            vertices.push(Vertex::new(ring, normal, [u, 1.0]));
 
-        for i in 0..segments {
-            let theta = i as f32 / segments as f32 * std::f32::consts::TAU;
-            let x = radius * theta.cos();
-            let z = radius * theta.sin();
-            let pos = Vec3::new(x, -height / 2.0, z);
-            vertices.push(Vertex::new(pos, Vec3::ZERO));
+            let tip = Vec3::new(0.0, height / 2.0, 0.0);
+            let tip_normal = Vec3::new(height * tip_theta.cos(), radius, height * tip_theta.sin()).normalize();
+            vertices.push(Vertex::new(tip, tip_normal, [u + 0.5 / segments as f32, 0.0]));
         }
 
         for i in 0..segments {
-            let next = if i + 1 < segments { i + 1 } else { 0 };
-            indices.push(0);
-            indices.push(2 + next);
-            indices.push(2 + i);
-        }
+            let ring = i * 2;
+            let tip = ring + 1;
+            let ring_next = ring + 2;
 
-        for i in 0..segments {
-            let next = if i + 1 < segments { i + 1 } else { 0 };
-            indices.push(1);
-            indices.push(2 + i);
-            indices.push(2 + next);
+            indices.extend_from_slice(&[tip, ring_next, ring]);
         }
 
+        push_cap(&mut vertices, &mut indices, segments, radius, -height / 2.0, -Vec3::Y);
+
         let mut mesh = Mesh::new(vertices, indices);
-        mesh.generate_normals();
-        
+        mesh.generate_tangents();
+        mesh.kind = MeshKind::Cone(segments);
+
         mesh
     }
 
+    /// Цилиндр радиусом 1 и высотой 1
     pub fn cylinder(segments: u16) -> Mesh {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
 
-        for &y in &[-0.5, 0.5] {
-            for &r in &[1.0, 0.0] {
-                for i in 0..segments {
-                    let theta = i as f32 / segments as f32 * std::f32::consts::TAU;
-                    let x = r * theta.cos();
-                    let z = r * theta.sin();
-                    vertices.push(Vertex::new(Vec3::new(x, y, z), Vec3::ZERO));
-                }
-            }
-        }
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let theta = u * std::f32::consts::TAU;
+            let normal = Vec3::new(theta.cos(), 0.0, theta.sin());
 
-        for i in 0..segments {
-            let next = (i + 1) % segments;
-            let outer_top = i;
-            let inner_top = segments + i;
-            let outer_top_next = next;
-            let inner_top_next = segments + next;
-
-            indices.push(outer_top);
-            indices.push(inner_top_next);
-            indices.push(inner_top);
-
-            indices.push(outer_top);
-            indices.push(outer_top_next);
-            indices.push(inner_top_next);
-        }
-
-        let base = 2 * segments;
-        for i in 0..segments {
-            let next = (i + 1) % segments;
-            let outer_bottom = base + i;
-            let inner_bottom = base + segments + i;
-            let outer_bottom_next = base + next;
-            let inner_bottom_next = base + segments + next;
-
-            indices.push(outer_bottom);
-            indices.push(inner_bottom);
-            indices.push(inner_bottom_next);
-
-            indices.push(outer_bottom);
-            indices.push(inner_bottom_next);
-            indices.push(outer_bottom_next);
+            vertices.push(Vertex::new(Vec3::new(normal.x, -0.5, normal.z), normal, [u, 1.0]));
+            vertices.push(Vertex::new(Vec3::new(normal.x, 0.5, normal.z), normal, [u, 0.0]));
         }
 
         for i in 0..segments {
-            let next = (i + 1) % segments;
-
-            let outer_top = i;
-            let outer_bottom = base + i;
-            let outer_top_next = next;
-            let outer_bottom_next = base + next;
-
-            indices.push(outer_top);
-            indices.push(outer_bottom);
-            indices.push(outer_bottom_next);
-
-            indices.push(outer_top);
-            indices.push(outer_bottom_next);
-            indices.push(outer_top_next);
-
-            let inner_top = segments + i;
-            let inner_bottom = base + segments + i;
-            let inner_top_next = segments + next;
-            let inner_bottom_next = base + segments + next;
-
-            indices.push(inner_top);
-            indices.push(inner_bottom_next);
-            indices.push(inner_bottom);
+            let bottom = i * 2;
+            let top = bottom + 1;
+            let bottom_next = bottom + 2;
+            let top_next = bottom + 3;
 
-            indices.push(inner_top);
-            indices.push(inner_top_next);
-            indices.push(inner_bottom_next);
+            indices.extend_from_slice(&[bottom, top, top_next, bottom, top_next, bottom_next]);
         }
 
+        push_cap(&mut vertices, &mut indices, segments, 1.0, 0.5, Vec3::Y);
+        push_cap(&mut vertices, &mut indices, segments, 1.0, -0.5, -Vec3::Y);
+
         let mut mesh = Mesh::new(vertices, indices);
-        mesh.generate_normals();
-        
+        mesh.generate_tangents();
+        mesh.kind = MeshKind::Cylinder(segments);
+
         mesh
     }
 
@@ -248,8 +281,8 @@ impl Mesh {
                 };
 
                 let normal = pos.normalize();
-                
-                vertices.push(Vertex::new(pos, normal));
+
+                vertices.push(Vertex::new(pos, normal, [u, v]));
             }
         }
 
@@ -267,6 +300,93 @@ impl Mesh {
             }
         }
 
-        Mesh { vertices, indices }
+        let mut mesh = Mesh { vertices, indices, kind: MeshKind::Sphere(segments) };
+        mesh.generate_tangents();
+
+        mesh
+    }
+}
+
+/// Круглая крышка в плоскости `y` с нормалью вверх или вниз.
+/// Текстура проецируется сверху
+fn push_cap(vertices: &mut Vec<Vertex>, indices: &mut Vec<u16>, segments: u16, radius: f32, y: f32, normal: Vec3) {
+    let center = vertices.len() as u16;
+    vertices.push(Vertex::new(Vec3::new(0.0, y, 0.0), normal, [0.5, 0.5]));
+
+    for i in 0..segments {
+        let theta = i as f32 / segments as f32 * std::f32::consts::TAU;
+        let (sin, cos) = theta.sin_cos();
+        let position = Vec3::new(radius * cos, y, radius * sin);
+        vertices.push(Vertex::new(position, normal, [0.5 + cos * 0.5, 0.5 + sin * 0.5]));
+    }
+
+    for i in 0..segments {
+        let ring = center + 1 + i;
+        let ring_next = center + 1 + (i + 1) % segments;
+
+        if normal.y > 0.0 {
+            indices.extend_from_slice(&[center, ring_next, ring]);
+        } else {
+            indices.extend_from_slice(&[center, ring, ring_next]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_direction(actual: [f32; 3], expected: Vec3) {
+        let difference = (actual[0] - expected.x).abs() + (actual[1] - expected.y).abs() + (actual[2] - expected.z).abs();
+        assert!(difference < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    /// Касательная идёт вдоль u, а зелёный канал карты нормалей смотрит
+    /// вверх по изображению (OpenGL). В шейдере зелёный умножается на
+    /// `-cross(n, t) * w`, потому что v в wgpu направлена вниз
+    #[test]
+    fn cube_tangents_follow_green_up_convention() {
+        let mesh = Mesh::cube();
+        assert!(mesh.has_tangents());
+
+        // Нормаль грани, направление u и верх изображения
+        let faces = [
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (-Vec3::Z, -Vec3::X, Vec3::Y),
+            (Vec3::X, -Vec3::Z, Vec3::Y),
+            (-Vec3::X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::X, -Vec3::Z),
+            (-Vec3::Y, Vec3::X, Vec3::Z)
+        ];
+
+        for vertex in &mesh.vertices {
+            let (normal, u, up) = faces
+                .into_iter()
+                .find(|(normal, _, _)| vertex.normal.dot(*normal) > 0.99)
+                .unwrap();
+
+            let [tx, ty, tz, w] = vertex.tangent;
+            let tangent = Vec3 { x: tx, y: ty, z: tz };
+            let green = normal.cross(tangent) * -w;
+
+            assert_direction([tx, ty, tz], u);
+            assert!(w == 1.0 || w == -1.0);
+            assert_direction([green.x, green.y, green.z], up);
+        }
+    }
+
+    #[test]
+    fn custom_meshes_start_without_tangents() {
+        let vertices = vec![
+            Vertex::new(Vec3::ZERO, Vec3::Z, [0.0, 1.0]),
+            Vertex::new(Vec3::X, Vec3::Z, [1.0, 1.0]),
+            Vertex::new(Vec3::Y, Vec3::Z, [0.0, 0.0])
+        ];
+        let mut mesh = Mesh::new(vertices, vec![0, 1, 2]);
+        assert!(!mesh.has_tangents());
+
+        mesh.generate_tangents();
+        assert!(mesh.has_tangents());
+        assert_direction(mesh.vertices[0].tangent[..3].try_into().unwrap(), Vec3::X);
     }
 }
@@ -3,3 +3,7 @@ pub mod transform;
 pub mod renderable;
 pub mod mesh;
 pub mod camera;
+pub mod settings;
+pub mod material;
+pub mod texture;
+pub mod shadows;
@@ -1,62 +1,92 @@
 use wgpu::*;
 use wgpu::util::*;
+use crate::engine::render::material::*;
 use crate::engine::render::mesh::*;
-use crate::engine::render::camera::*;
+use crate::engine::render::texture::*;
 use crate::engine::render::transform::*;
 use crate::engine::core::primitives::*;
 
-/// Униформа
+/// Униформа кадра: камера
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Uniforms {
-    pub model: Mat4,
+pub struct FrameUniforms {
     pub view: Mat4,
     pub projection: Mat4,
-    pub normal: Mat4,
     pub camera_pos: Vec3,
-    pub _padding1: f32,
-    pub light_pos: Vec3,
-    pub light_far_plane: f32,
-    pub light_view_projection: Mat4
+    pub _padding1: f32
 }
 
-impl Default for Uniforms {
+impl Default for FrameUniforms {
     fn default() -> Self {
         Self {
-            model: Mat4::default(),
             view: Mat4::default(),
             projection: Mat4::default(),
-            normal: Mat4::default(),
             camera_pos: Vec3::ZERO,
-            _padding1: 0.0,
-            light_pos: Vec3::ZERO,
-            light_far_plane: 100.0,
-            light_view_projection: Mat4::default()
+            _padding1: 0.0
         }
     }
 }
 
-/// Освещение
+/// Униформа объекта. Перезаписывается только при изменении позиционирования
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectUniforms {
+    pub model: Mat4,
+    pub normal: Mat4
+}
+
+/// Униформа прохода теней, одна на слой
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniforms {
+    pub light_view_projection: Mat4
+}
+
+/// Типы источников освещения
+pub const LIGHT_POINT: u32 = 0;
+/// Бесконечно удалённый источник без затухания, светит вдоль `direction`
+pub const LIGHT_DIRECTIONAL: u32 = 1;
+/// Конус вдоль `direction` с плавным краем между `inner_angle` и `outer_angle`
+pub const LIGHT_SPOT: u32 = 2;
+
+/// Наибольший внешний угол прожектора в градусах, конус уже полусферы
+pub const MAX_SPOT_ANGLE: f32 = 89.0;
+
+/// Освещение. Раскладка совпадает со структурой `Light` в шейдере
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Light {
+    /// Вычисляется из мирового положения сущности
     pub position: Vec3,
     pub light_type: u32,
     pub color: Vec3,
     pub intensity: f32,
+    /// Вычисляется из поворота сущности: её ось -Z
+    pub direction: Vec3,
     pub range: f32,
-    pub _pad: [f32; 6]
+    /// Слой карты теней, назначается рендерером. -1 - без тени
+    pub shadow_index: i32,
+    /// Углы от оси конуса прожектора в градусах: до внутреннего свет полный,
+    /// после внешнего его нет
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    /// Отбрасывает ли источник тени. 0 - нет, иначе да
+    pub casts_shadows: u32
 }
 
 impl Default for Light {
     fn default() -> Self {
         Self {
             position: Vec3::ZERO,
-            light_type: 0,
+            light_type: LIGHT_POINT,
             color: Vec3::IDENTITY,
             intensity: 1.0,
+            direction: -Vec3::Z,
             range: 100.0,
-            _pad: [0.0; 6]
+            shadow_index: -1,
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+            casts_shadows: 1
         }
     }
 }
@@ -64,14 +94,50 @@ impl Default for Light {
 impl Light {
     pub fn new(light_type: u32, color: Vec3, intensity: f32, range: f32) -> Self {
         Self {
-            position: Vec3::ZERO,
             light_type,
             color,
             intensity,
             range,
-            _pad: [0.0; 6]
+            ..Self::default()
         }
     }
+
+    /// Дальность для направленного света не используется
+    pub fn directional(color: Vec3, intensity: f32) -> Self {
+        Self {
+            light_type: LIGHT_DIRECTIONAL,
+            color,
+            intensity,
+            ..Self::default()
+        }
+    }
+
+    pub fn spot(color: Vec3, intensity: f32, range: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        Self {
+            light_type: LIGHT_SPOT,
+            color,
+            intensity,
+            range,
+            ..Self::default()
+        }.with_angles(inner_angle, outer_angle)
+    }
+
+    /// Углы конуса приводятся к `0 <= inner <= outer <= MAX_SPOT_ANGLE`
+    pub fn with_angles(mut self, inner_angle: f32, outer_angle: f32) -> Self {
+        self.outer_angle = outer_angle.clamp(0.0, MAX_SPOT_ANGLE);
+        self.inner_angle = inner_angle.clamp(0.0, self.outer_angle);
+        self
+    }
+
+    /// Тени от источника. Слои выделяются только отбрасывающим тени
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows as u32;
+        self
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        self.casts_shadows != 0
+    }
 }
 
 /// Количество источников освещения
@@ -81,26 +147,27 @@ pub struct LightCount {
     pub count: u32
 }
 
-/// Рендерная сетка
+/// Рендерная сетка. Создаётся рендерером по компоненту `Mesh`, материал
+/// берётся из компонента `Material`
 pub struct RenderableMesh {
     pub vertex_buffer: Buffer,
     pub index_buffer: Option<Buffer>,
     pub index_count: u32,
     pub uniform_buffer: Buffer,
-    pub main_bind_group: BindGroup,
-    pub shadow_bind_group: BindGroup
+    pub bind_group: BindGroup,
+    pub material_buffer: Buffer,
+    pub material_bind_group: BindGroup
 }
 
 impl RenderableMesh {
     pub fn new(
         device: &Device,
-        bind_group_layout: &BindGroupLayout,
-        shadow_bind_group_layout: &BindGroupLayout,
+        object_bind_group_layout: &BindGroupLayout,
+        material_bind_group_layout: &BindGroupLayout,
         mesh: &Mesh,
-        shadow_view: &TextureView,
-        shadow_sampler: &Sampler,
-        light_buffer: &Buffer,
-        light_count_buffer: &Buffer
+        material: &Material,
+        base_color_texture: &TextureAsset,
+        normal_texture: &TextureAsset
     ) -> Self {
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Vertex Buffer"),
@@ -118,108 +185,116 @@ impl RenderableMesh {
             None
         };
 
-        let uniform_data = Uniforms::default();
-
         let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Uniform Buffer"),
-            contents: bytemuck::bytes_of(&uniform_data),
+            label: Some("Object Uniform Buffer"),
+            contents: bytemuck::bytes_of(&ObjectUniforms::default()),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
         });
 
-        let main_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: bind_group_layout,
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: object_bind_group_layout,
             entries: &[
-                BindGroupEntry {    
+                BindGroupEntry {
                     binding: 0,
                     resource: uniform_buffer.as_entire_binding()
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: light_buffer.as_entire_binding()
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: light_count_buffer.as_entire_binding()
-                },
-                BindGroupEntry {
-                    binding: 3,
-                    resource: BindingResource::TextureView(shadow_view)
-                },
-                BindGroupEntry {
-                    binding: 4,
-                    resource: BindingResource::Sampler(shadow_sampler)
                 }
             ],
-            label: Some("uniform_bind_group")
+            label: Some("object_bind_group")
         });
 
-        let shadow_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            layout: shadow_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding()
-                }
-            ],
-            label: Some("shadow_bind_group")
+        let material_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Material Uniform Buffer"),
+            contents: bytemuck::bytes_of(&material.uniforms()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
         });
 
+        let material_bind_group = create_material_bind_group(
+            device,
+            material_bind_group_layout,
+            &material_buffer,
+            base_color_texture,
+            normal_texture
+        );
+
         Self {
             vertex_buffer,
             index_buffer,
             index_count: mesh.index_count(),
             uniform_buffer,
-            main_bind_group,
-            shadow_bind_group
+            bind_group,
+            material_buffer,
+            material_bind_group
         }
     }
 
-    pub fn update_uniforms(
-        &self,
-        queue: &Queue,
-        transform: &Transform,
-        camera: &Camera,
-        aspect_ratio: f32,
-        light_pos: Vec3,
-        light_far_plane: f32,
-        light_view_projection: Mat4
-    ) {
-        let uniforms = Uniforms {
-            model: Mat4::from_transform(transform),
-            view: camera.get_view_matrix(),
-            projection: camera.get_projection_matrix(aspect_ratio),
-            normal: Mat4::from_transform(transform).inverse().transpose(),
-            camera_pos: camera.position,
-            _padding1: 0.0,
-            light_pos,
-            light_far_plane,
-            light_view_projection
+    pub fn update_uniforms(&self, queue: &Queue, transform: &Transform) {
+        let model = Mat4::from_transform(transform);
+        let uniforms = ObjectUniforms {
+            model,
+            normal: model.inverse().transpose()
         };
 
         queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
     }
 
-    pub fn update_uniforms_for_shadow(
-        &self,
-        queue: &Queue,
-        transform: &Transform,
-        light_view_projection: Mat4,
-        light_far_plane: f32
-    ) {
-        let model = Mat4::from_transform(transform);
+    pub fn update_material(&self, queue: &Queue, material: &Material) {
+        queue.write_buffer(&self.material_buffer, 0, bytemuck::bytes_of(&material.uniforms()));
+    }
 
-        let uniform_data = Uniforms {
-            model,
-            view: Mat4::IDENTITY,
-            projection: Mat4::IDENTITY,
-            normal: Mat4::IDENTITY,
-            camera_pos: Vec3::ZERO,
-            _padding1: 0.0,
-            light_pos: Vec3::ZERO,
-            light_far_plane,
-            light_view_projection
-        };
+    /// Смена текстур материала пересоздаёт его группу
+    pub fn bind_textures(&mut self, device: &Device, material_bind_group_layout: &BindGroupLayout, base_color_texture: &TextureAsset, normal_texture: &TextureAsset) {
+        self.material_bind_group = create_material_bind_group(
+            device,
+            material_bind_group_layout,
+            &self.material_buffer,
+            base_color_texture,
+            normal_texture
+        );
+    }
 
-        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform_data));
+    pub fn draw<'p>(&self, pass: &mut RenderPass<'p>) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        if let Some(index_buffer) = &self.index_buffer {
+            pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
+            pass.draw_indexed(0..self.index_count, 0, 0..1);
+        } else {
+            pass.draw(0..self.index_count, 0..1);
+        }
     }
 }
+
+fn create_material_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    material_buffer: &Buffer,
+    base_color_texture: &TextureAsset,
+    normal_texture: &TextureAsset
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: material_buffer.as_entire_binding()
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(&base_color_texture.view)
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(&base_color_texture.sampler)
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&normal_texture.view)
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::Sampler(&normal_texture.sampler)
+            }
+        ],
+        label: Some("material_bind_group")
+    })
+}
@@ -1,33 +1,62 @@
 use std::sync::Arc;
+use std::sync::mpsc;
+use image::RgbaImage;
 use wgpu::*;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use winit::window::Window;
 use crate::engine::ecs::*;
+use crate::engine::render::camera::*;
+use crate::engine::render::material::*;
+use crate::engine::render::mesh::*;
 use crate::engine::render::renderable::*;
+use crate::engine::render::settings::*;
+use crate::engine::render::shadows::*;
+use crate::engine::render::texture::*;
+use crate::engine::render::transform::*;
 use std::mem::size_of;
 use wgpu::StoreOp::Store;
-use crate::engine::core::primitives::*;
 
 /// Максимальное число источников света. Обновлять вместе с шейдером
 const MAX_LIGHTS: usize = 100;
-/// Разрешение теней
-const SHADOW_RESOLUTION: usize = 1024;
+
+/// Куда выводится кадр
+enum RenderTarget<'a> {
+    /// Поверхность окна
+    Surface {
+        surface: Surface<'a>,
+        config: SurfaceConfiguration
+    },
+    /// Текстура в памяти видеокарты, кадр из неё читается `read_pixels`
+    Offscreen {
+        texture: Texture
+    }
+}
 
 /// Рендерер
 pub struct Renderer<'a> {
-    surface: Surface<'a>,
+    target: RenderTarget<'a>,
     pub device: Device,
     pub queue: Queue,
-    config: SurfaceConfiguration,
+    format: TextureFormat,
     size: winit::dpi::PhysicalSize<u32>,
     pub render_pipeline: RenderPipeline,
     pub shadow_pipeline: RenderPipeline,
     pub depth_view: TextureView,
-    pub shadow_cube_view: TextureView,
-    pub shadow_cube_faces: Vec<TextureView>,
+    pub shadow_maps: ShadowMaps,
     pub shadow_sampler: Sampler,
     pub light_buffer: Buffer,
-    pub light_count_buffer: Buffer
+    pub light_count_buffer: Buffer,
+    pub object_bind_group_layout: BindGroupLayout,
+    pub material_bind_group_layout: BindGroupLayout,
+    pub textures: Textures,
+    frame_buffer: Buffer,
+    frame_bind_group_layout: BindGroupLayout,
+    frame_bind_group: BindGroup,
+    shadow_bind_group_layout: BindGroupLayout,
+    /// Источники света с прошлого изменения
+    lights: Vec<Light>,
+    /// Такт последней синхронизации с ECS
+    last_tick: u32
 }
 
 impl<'a> Renderer<'a> {
@@ -66,38 +95,40 @@ impl<'a> Renderer<'a> {
         };
         surface.configure(&device, &config);
 
-        let depth_format = TextureFormat::Depth32Float;
-
-        let shadow_cube_texture = device.create_texture(&TextureDescriptor {
-            label: Some("Shadow Cube Texture"),
-            size: Extent3d {
-                width: SHADOW_RESOLUTION as u32,
-                height: SHADOW_RESOLUTION as u32,
-                depth_or_array_layers: 6
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[]
-        });
+        Self::with_target(device, queue, RenderTarget::Surface { surface, config }, surface_format, size)
+    }
 
-        let shadow_cube_view = shadow_cube_texture.create_view(&TextureViewDescriptor {
-            label: Some("Shadow Cube View"),
-            dimension: Some(TextureViewDimension::Cube),
+    /// Рендер без окна в текстуру заданного размера. `software` выбирает
+    /// программный адаптер вроде llvmpipe или lavapipe, что позволяет
+    /// рисовать без видеокарты и дисплея
+    pub async fn headless(width: u32, height: u32, software: bool) -> Result<Self, String> {
+        let instance = Instance::new(&InstanceDescriptor {
+            backends: Backends::all(),
             ..Default::default()
         });
 
-        let shadow_cube_faces = (0..6).map(|i| {
-            shadow_cube_texture.create_view(&TextureViewDescriptor {
-                label: Some(&format!("Shadow Face {}", i)),
-                dimension: Some(TextureViewDimension::D2),
-                base_array_layer: i,
-                array_layer_count: Some(1),
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                force_fallback_adapter: software,
                 ..Default::default()
             })
-        }).collect::<Vec<_>>();
+            .await
+            .map_err(|e| format!("Нет подходящего графического адаптера: {}", e))?;
+
+        let (device, queue) = adapter
+            .request_device(&DeviceDescriptor::default())
+            .await
+            .map_err(|e| format!("Не удалось создать логическое устройство: {}", e))?;
+
+        let size = winit::dpi::PhysicalSize::new(width.max(1), height.max(1));
+        let format = TextureFormat::Rgba8UnormSrgb;
+        let texture = create_offscreen_texture(&device, format, size.width, size.height);
+
+        Ok(Self::with_target(device, queue, RenderTarget::Offscreen { texture }, format, size))
+    }
+
+    fn with_target(device: Device, queue: Queue, target: RenderTarget<'a>, format: TextureFormat, size: winit::dpi::PhysicalSize<u32>) -> Self {
+        let depth_format = TextureFormat::Depth32Float;
 
         let shadow_sampler = device.create_sampler(&SamplerDescriptor {
             label: Some("Shadow Sampler"),
@@ -109,21 +140,7 @@ impl<'a> Renderer<'a> {
             ..Default::default()
         });
 
-        let depth_texture = device.create_texture(&TextureDescriptor {
-            label: Some("Main Depth Texture"),
-            size: Extent3d {
-                width: config.width,
-                height: config.height,
-                depth_or_array_layers: 1
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[]
-        });
-        let depth_view = depth_texture.create_view(&TextureViewDescriptor::default());
+        let depth_view = create_depth_view(&device, size.width, size.height);
 
         let light_buffer = device.create_buffer(&BufferDescriptor {
             label: Some("Light Buffer"),
@@ -139,7 +156,7 @@ impl<'a> Renderer<'a> {
         });
 
         let vertex_layout = VertexBufferLayout {
-            array_stride: size_of::<[f32; 6]>() as u64,
+            array_stride: size_of::<Vertex>() as u64,
             step_mode: VertexStepMode::Vertex,
             attributes: &[
                 VertexAttribute {
@@ -151,6 +168,16 @@ impl<'a> Renderer<'a> {
                     offset: size_of::<[f32; 3]>() as u64,
                     shader_location: 1,
                     format: VertexFormat::Float32x3
+                },
+                VertexAttribute {
+                    offset: size_of::<[f32; 6]>() as u64,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x2
+                },
+                VertexAttribute {
+                    offset: size_of::<[f32; 8]>() as u64,
+                    shader_location: 3,
+                    format: VertexFormat::Float32x4
                 }
             ]
         };
@@ -165,10 +192,16 @@ impl<'a> Renderer<'a> {
             source: ShaderSource::Wgsl(include_str!("../shaders/shadow.wgsl").into())
         });
 
-        let uniform_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("Uniform Bind Group Layout"),
+        let frame_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Frame Uniform Buffer"),
+            contents: bytemuck::bytes_of(&FrameUniforms::default()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        });
+
+        let frame_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Frame Bind Group Layout"),
             entries: &[
-                // 0 - Uniforms (model/view/proj)
+                // 0 - Uniforms (view/proj)
                 BindGroupLayoutEntry {
                     binding: 0,
                     visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
@@ -201,13 +234,13 @@ impl<'a> Renderer<'a> {
                     },
                     count: None
                 },
-                // 3 - Shadow texture
+                // 3 - Shadow map layers
                 BindGroupLayoutEntry {
                     binding: 3,
                     visibility: ShaderStages::FRAGMENT,
                     ty: BindingType::Texture {
                         multisampled: false,
-                        view_dimension: TextureViewDimension::Cube,
+                        view_dimension: TextureViewDimension::D2Array,
                         sample_type: TextureSampleType::Depth
                     },
                     count: None
@@ -218,6 +251,17 @@ impl<'a> Renderer<'a> {
                     visibility: ShaderStages::FRAGMENT,
                     ty: BindingType::Sampler(SamplerBindingType::Comparison),
                     count: None
+                },
+                // 5 - Shadow layer matrices
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
                 }
             ]
         });
@@ -225,7 +269,7 @@ impl<'a> Renderer<'a> {
         let shadow_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("Shadow Bind Group Layout"),
             entries: &[
-                // 0 - Uniforms
+                // 0 - Light view/proj
                 BindGroupLayoutEntry {
                     binding: 0,
                     visibility: ShaderStages::VERTEX,
@@ -239,15 +283,100 @@ impl<'a> Renderer<'a> {
             ]
         });
 
+        // Общий для основного прохода и прохода теней
+        let object_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Object Bind Group Layout"),
+            entries: &[
+                // 0 - Uniforms (model/normal)
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ]
+        });
+
+        let shadow_maps = ShadowMaps::new(&device, &shadow_bind_group_layout, RenderSettings::default().shadow_layers);
+
+        let material_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Material Bind Group Layout"),
+            entries: &[
+                // 0 - Material uniforms
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                },
+                // 1 - Base color texture
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                // 2 - Base color sampler
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None
+                },
+                // 3 - Normal texture
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                // 4 - Normal sampler
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+
+        let textures = Textures::new(&device, &queue);
+
+        let frame_bind_group = create_frame_bind_group(
+            &device,
+            &frame_bind_group_layout,
+            &frame_buffer,
+            &light_buffer,
+            &light_count_buffer,
+            &shadow_maps,
+            &shadow_sampler
+        );
+
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&uniform_bind_group_layout],
+            bind_group_layouts: &[&frame_bind_group_layout, &object_bind_group_layout, &material_bind_group_layout],
             push_constant_ranges: &[]
         });
 
         let shadow_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Shadow Pipeline Layout"),
-            bind_group_layouts: &[&shadow_bind_group_layout],
+            bind_group_layouts: &[&shadow_bind_group_layout, &object_bind_group_layout],
             push_constant_ranges: &[]
         });
 
@@ -257,14 +386,14 @@ impl<'a> Renderer<'a> {
             vertex: VertexState {
                 module: &shader,
                 entry_point: Option::from("vs_main"),
-                buffers: &[vertex_layout.clone()],
+                buffers: std::slice::from_ref(&vertex_layout),
                 compilation_options: Default::default()
             },
             fragment: Some(FragmentState {
                 module: &shader,
                 entry_point: Option::from("fs_main"),
                 targets: &[Some(ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(BlendState::REPLACE),
                     write_mask: ColorWrites::ALL
                 })],
@@ -314,102 +443,310 @@ impl<'a> Renderer<'a> {
         });
 
         Self {
-            surface,
+            target,
             device,
             queue,
-            config,
+            format,
             size,
             render_pipeline,
             shadow_pipeline,
             depth_view,
-            shadow_cube_view,
-            shadow_cube_faces,
+            shadow_maps,
             shadow_sampler,
             light_buffer,
-            light_count_buffer
+            light_count_buffer,
+            object_bind_group_layout,
+            material_bind_group_layout,
+            textures,
+            frame_buffer,
+            frame_bind_group_layout,
+            frame_bind_group,
+            shadow_bind_group_layout,
+            lights: Vec::new(),
+            last_tick: 0
         }
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+
+        self.size = new_size;
+        self.depth_view = create_depth_view(&self.device, new_size.width, new_size.height);
+
+        match &mut self.target {
+            RenderTarget::Surface { surface, config } => {
+                config.width = new_size.width;
+                config.height = new_size.height;
+                surface.configure(&self.device, config);
+            }
+            RenderTarget::Offscreen { texture } => {
+                *texture = create_offscreen_texture(&self.device, self.format, new_size.width, new_size.height);
+            }
         }
     }
 
-    pub fn render(&mut self, ecs: &mut ECS) -> Result<(), SurfaceError> {
-        let aspect_ratio = self.size.width as f32 / self.size.height as f32;
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
 
-        let frame = self.surface.get_current_texture()?;
-        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen { .. })
+    }
+
+    /// Синхронизация с ECS: буферы создаются и обновляются только для сеток,
+    /// позиционирования, материалов и источников света, изменённых с прошлого кадра
+    pub fn prepare(&mut self, ecs: &mut ECS) {
+        let since = self.last_tick;
+
+        let removed = ecs.removed::<Mesh>(since).collect::<Vec<_>>();
+        for entity in removed {
+            if !ecs.has::<Mesh>(entity) {
+                ecs.remove::<RenderableMesh>(entity);
+            }
+        }
 
-        let lights = ecs.collect_lights();
-        let light_count = LightCount { count: lights.len() as u32 };
+        let changed_meshes = ecs.changed::<Mesh>(since).collect::<Vec<_>>();
+        for &entity in &changed_meshes {
+            let Some(mesh) = ecs.get::<Mesh>(entity) else {
+                continue;
+            };
+
+            // Произвольной сетке без касательных карта нормалей иначе не применится
+            let generated;
+            let mesh = if mesh.has_tangents() {
+                mesh
+            } else {
+                let mut copy = mesh.clone();
+                copy.generate_tangents();
+                generated = copy;
+                &generated
+            };
+
+            let material = ecs.get::<Material>(entity).cloned().unwrap_or_default();
+            let (base_color_texture, normal_texture) = self.material_textures(&material);
+            let renderable = RenderableMesh::new(
+                &self.device,
+                &self.object_bind_group_layout,
+                &self.material_bind_group_layout,
+                mesh,
+                &material,
+                &base_color_texture,
+                &normal_texture
+            );
+            if let Some(transform) = ecs.get::<GlobalTransform>(entity) {
+                renderable.update_uniforms(&self.queue, &transform.0);
+            }
 
-        self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&lights));
+            ecs.insert(entity, renderable);
+        }
+
+        for entity in ecs.changed::<GlobalTransform>(since) {
+            if changed_meshes.contains(&entity) {
+                continue;
+            }
+
+            if let (Some(renderable), Some(transform)) = (ecs.get::<RenderableMesh>(entity), ecs.get::<GlobalTransform>(entity)) {
+                renderable.update_uniforms(&self.queue, &transform.0);
+            }
+        }
+
+        // Без компонента сетка возвращается к материалу по умолчанию
+        let changed_materials = ecs
+            .changed::<Material>(since)
+            .chain(ecs.removed::<Material>(since))
+            .filter(|entity| !changed_meshes.contains(entity))
+            .collect::<Vec<_>>();
+
+        for entity in changed_materials {
+            if !ecs.has::<RenderableMesh>(entity) {
+                continue;
+            }
+
+            let material = ecs.get::<Material>(entity).cloned().unwrap_or_default();
+            let (base_color_texture, normal_texture) = self.material_textures(&material);
+
+            if let Some(renderable) = ecs.get_mut::<RenderableMesh>(entity) {
+                renderable.update_material(&self.queue, &material);
+                renderable.bind_textures(&self.device, &self.material_bind_group_layout, &base_color_texture, &normal_texture);
+            }
+        }
+
+        let lights_moved = ecs
+            .changed::<GlobalTransform>(since)
+            .any(|entity| ecs.has::<Light>(entity));
+
+        if lights_moved || ecs.changed::<Light>(since).next().is_some() || ecs.removed::<Light>(since).next().is_some() {
+            self.lights = ecs.collect_lights();
+            self.lights.truncate(MAX_LIGHTS);
+            self.upload_lights();
+        }
+
+        self.last_tick = ecs.change_tick();
+    }
+
+    /// Текстуры материала или заглушки для незаданных
+    fn material_textures(&mut self, material: &Material) -> (TextureAsset, TextureAsset) {
+        let base_color_texture = match &material.base_color_texture {
+            Some(path) => self.textures.get_or_load(&self.device, &self.queue, path, TextureKind::Color, material.sampler),
+            None => self.textures.white.clone()
+        };
+
+        let normal_texture = match &material.normal_texture {
+            Some(path) => self.textures.get_or_load(&self.device, &self.queue, path, TextureKind::Normal, material.sampler),
+            None => self.textures.flat_normal.clone()
+        };
+
+        (base_color_texture, normal_texture)
+    }
+
+    /// Пересоздание карт теней под новый бюджет слоёв. Бюджет больше
+    /// `max_texture_array_layers` устройства урезается
+    fn set_shadow_budget(&mut self, layers: u32) {
+        let layers = layers.min(self.device.limits().max_texture_array_layers);
+
+        if self.shadow_maps.layers() == layers {
+            return;
+        }
+
+        self.shadow_maps = ShadowMaps::new(&self.device, &self.shadow_bind_group_layout, layers);
+        self.frame_bind_group = create_frame_bind_group(
+            &self.device,
+            &self.frame_bind_group_layout,
+            &self.frame_buffer,
+            &self.light_buffer,
+            &self.light_count_buffer,
+            &self.shadow_maps,
+            &self.shadow_sampler
+        );
+
+        self.upload_lights();
+    }
+
+    /// Назначение слоёв теней и запись источников в буферы
+    fn upload_lights(&mut self) {
+        self.shadow_maps.allocate(&mut self.lights);
+
+        let light_count = LightCount { count: self.lights.len() as u32 };
+        self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&self.lights));
         self.queue.write_buffer(&self.light_count_buffer, 0, bytemuck::bytes_of(&light_count));
+    }
+
+    /// Кадр в окно или, без окна, в текстуру
+    pub fn render(&mut self, ecs: &mut ECS) -> Result<(), SurfaceError> {
+        let (frame, view) = match &self.target {
+            RenderTarget::Surface { surface, .. } => {
+                let frame = surface.get_current_texture()?;
+                let view = frame.texture.create_view(&TextureViewDescriptor::default());
+                (Some(frame), view)
+            }
+            RenderTarget::Offscreen { texture } => (None, texture.create_view(&TextureViewDescriptor::default()))
+        };
+
+        self.draw(ecs, &view);
 
-        if lights.is_empty() {
-            return Ok(());
+        if let Some(frame) = frame {
+            frame.present();
         }
-        
-        // TODO: сделать поддержку нескольких источников света
-        let light_pos = Vec3::from(lights[0].position);
-        let light_far_plane = 100.0;
-
-        let directions = [
-            (Vec3::X, -Vec3::Y),
-            (-Vec3::X, -Vec3::Y),
-            (Vec3::Y, Vec3::Z),
-            (-Vec3::Y, -Vec3::Z),
-            (Vec3::Z, -Vec3::Y),
-            (-Vec3::Z, -Vec3::Y)
-        ];
-
-        let proj = Mat4::perspective(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 1000.0);
-        let light_matrices: Vec<Mat4> = directions.iter().map(|(dir, up)| {
-            let view = Mat4::look_at(light_pos, light_pos + *dir, *up);
-            proj * view
-        }).collect();
 
-        let mut shadow_encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Shadow Encoder")
+        Ok(())
+    }
+
+    /// Кадр без окна, сразу прочитанный в память
+    pub fn render_to_image(&mut self, ecs: &mut ECS) -> Result<RgbaImage, String> {
+        self.render(ecs).map_err(|e| e.to_string())?;
+        self.read_pixels()
+    }
+
+    /// Копия последнего кадра в памяти. Только для рендера без окна
+    pub fn read_pixels(&self) -> Result<RgbaImage, String> {
+        let RenderTarget::Offscreen { texture } = &self.target else {
+            return Err("Чтение кадра доступно только без окна".to_string());
+        };
+
+        let (width, height) = (self.size.width, self.size.height);
+        // Строки копии выравниваются по 256 байт
+        let row_bytes = 4 * width;
+        let padded_row_bytes = row_bytes.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row_bytes * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false
         });
 
-        for face in 0..6 {
-            let mut shadow_pass = shadow_encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some(&format!("Shadow Pass Face {}", face)),
-                color_attachments: &[],
-                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: &self.shadow_cube_faces[face],
-                    depth_ops: Some(Operations {
-                        load: LoadOp::Clear(1.0),
-                        store: Store
-                    }),
-                    stencil_ops: None
-                }),
-                ..Default::default()
-            });
+        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Readback Encoder")
+        });
 
-            shadow_pass.set_pipeline(&self.shadow_pipeline);
-
-            for (entity, renderable) in &ecs.renderables {
-                if let Some(transform) = ecs.transforms.get(entity) {
-                    renderable.update_uniforms_for_shadow(&self.queue, transform, light_matrices[face], light_far_plane);
-                    shadow_pass.set_bind_group(0, &renderable.shadow_bind_group, &[]);
-                    shadow_pass.set_vertex_buffer(0, renderable.vertex_buffer.slice(..));
-                    
-                    if let Some(index_buffer) = &renderable.index_buffer {
-                        shadow_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
-                        shadow_pass.draw_indexed(0..renderable.index_count, 0, 0..1);
-                    } else {
-                        shadow_pass.draw(0..renderable.index_count, 0..1);
-                    }
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height)
                 }
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            }
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        self.device.poll(PollType::Wait).map_err(|e| e.to_string())?;
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_row_bytes as usize) {
+                pixels.extend_from_slice(&row[..row_bytes as usize]);
             }
         }
+        buffer.unmap();
+
+        RgbaImage::from_raw(width, height, pixels).ok_or_else(|| "Неверный размер кадра".to_string())
+    }
+
+    fn draw(&mut self, ecs: &mut ECS, view: &TextureView) {
+        let aspect_ratio = self.size.width as f32 / self.size.height as f32;
+        let camera = ecs.resource::<Camera>().clone();
+        let settings = ecs.get_resource::<RenderSettings>().cloned().unwrap_or_default();
+
+        self.set_shadow_budget(settings.shadow_layers);
+        self.prepare(ecs);
+
+        self.shadow_maps.update(&self.queue, &self.lights, camera.position, settings.directional_shadow_extent);
+
+        let frame_uniforms = FrameUniforms {
+            view: camera.get_view_matrix(),
+            projection: camera.get_projection_matrix(aspect_ratio),
+            camera_pos: camera.position,
+            _padding1: 0.0
+        };
+        self.queue.write_buffer(&self.frame_buffer, 0, bytemuck::bytes_of(&frame_uniforms));
+
+        let mut shadow_encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Shadow Encoder")
+        });
+
+        self.shadow_maps.render(&mut shadow_encoder, &self.shadow_pipeline, ecs);
 
         self.queue.submit(Some(shadow_encoder.finish()));
 
@@ -421,10 +758,10 @@ impl<'a> Renderer<'a> {
             let mut render_pass = main_encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("Main Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: Operations {
-                        load: LoadOp::Clear(Color::BLACK),
+                        load: LoadOp::Clear(settings.clear_color),
                         store: Store
                     }
                 })],
@@ -440,40 +777,140 @@ impl<'a> Renderer<'a> {
             });
 
             render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, &self.frame_bind_group, &[]);
 
-            for (entity, renderable) in &ecs.renderables {
-                if let Some(transform) = ecs.transforms.get(entity) {
-                    renderable.update_uniforms(
-                        &self.queue,
-                        transform,
-                        &ecs.camera.clone().unwrap(),
-                        aspect_ratio,
-                        light_pos,
-                        light_far_plane,
-                        light_matrices[0]
-                    );
-
-                    render_pass.set_bind_group(0, &renderable.main_bind_group, &[]);
-                    render_pass.set_vertex_buffer(0, renderable.vertex_buffer.slice(..));
-                    
-                    if let Some(index_buffer) = &renderable.index_buffer {
-                        render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
-                        render_pass.draw_indexed(0..renderable.index_count, 0, 0..1);
-                    } else {
-                        render_pass.draw(0..renderable.index_count, 0..1);
-                    }
-                }
+            for (_, renderable) in ecs.query::<RenderableMesh>() {
+                render_pass.set_bind_group(1, &renderable.bind_group, &[]);
+                render_pass.set_bind_group(2, &renderable.material_bind_group, &[]);
+                renderable.draw(&mut render_pass);
             }
         }
 
         self.queue.submit(Some(main_encoder.finish()));
-        frame.present();
 
-        // TODO: убрать после отладки
-        if let Some(camera) = &ecs.camera {
+        if settings.log_camera {
             println!("Pos: {:?} | Rot: {:?} | FOV: {}", camera.position, camera.rotation, camera.fov);
         }
+    }
+}
 
-        Ok(())
+fn create_depth_view(device: &Device, width: u32, height: u32) -> TextureView {
+    let depth_texture = device.create_texture(&TextureDescriptor {
+        label: Some("Main Depth Texture"),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Depth32Float,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[]
+    });
+
+    depth_texture.create_view(&TextureViewDescriptor::default())
+}
+
+/// Цель рендера без окна, из неё же копируется кадр
+fn create_offscreen_texture(device: &Device, format: TextureFormat, width: u32, height: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some("Offscreen Texture"),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        view_formats: &[]
+    })
+}
+
+/// Группа кадра ссылается на карты теней и пересоздаётся вместе с ними
+fn create_frame_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    frame_buffer: &Buffer,
+    light_buffer: &Buffer,
+    light_count_buffer: &Buffer,
+    shadow_maps: &ShadowMaps,
+    shadow_sampler: &Sampler
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: frame_buffer.as_entire_binding()
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: light_buffer.as_entire_binding()
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: light_count_buffer.as_entire_binding()
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&shadow_maps.view)
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::Sampler(shadow_sampler)
+            },
+            BindGroupEntry {
+                binding: 5,
+                resource: shadow_maps.matrix_buffer.as_entire_binding()
+            }
+        ],
+        label: Some("frame_bind_group")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::core::primitives::*;
+
+    /// Ширина не кратна 64 пикселям: строки копии дополняются до 256 байт
+    const WIDTH: u32 = 100;
+    const HEIGHT: u32 = 60;
+
+    #[test]
+    #[ignore = "нужен GPU-адаптер, запуск: cargo test -- --ignored"]
+    fn headless_frame_matches_scene() {
+        let mut renderer = pollster::block_on(Renderer::headless(WIDTH, HEIGHT, true)).unwrap();
+
+        let mut ecs = ECS::new();
+        ecs.insert_resource(RenderSettings { clear_color: Color::BLUE, ..RenderSettings::default() });
+
+        // Светящийся куб прямо перед камерой, источников света нет
+        let cube = ecs.create_entity();
+        ecs.add_transform(cube, Transform::new(Vec3::new(0.0, 0.0, -3.0), Quat::IDENTITY, Vec3::IDENTITY));
+        ecs.add_mesh(cube, Mesh::cube());
+        ecs.add_material(cube, Material::new(Vec3::ZERO).with_emissive(Vec3::X));
+
+        let image = renderer.render_to_image(&mut ecs).unwrap();
+        assert_eq!(image.dimensions(), (WIDTH, HEIGHT));
+
+        for (x, y) in [(0, 0), (WIDTH - 1, 0), (0, HEIGHT - 1), (WIDTH - 1, HEIGHT - 1)] {
+            assert_eq!(image.get_pixel(x, y).0, [0, 0, 255, 255], "фон в ({}, {})", x, y);
+        }
+
+        let center = image.get_pixel(WIDTH / 2, HEIGHT / 2).0;
+        assert!(center[0] > 200 && center[1] < 20 && center[2] < 20, "центр {:?}", center);
+
+        // Сдвиг строк при неверном шаге дал бы несимметричный куб
+        for y in [HEIGHT / 2 - 5, HEIGHT / 2, HEIGHT / 2 + 5] {
+            let red = (0..WIDTH).filter(|x| image.get_pixel(*x, y).0[0] > 128).collect::<Vec<_>>();
+            let (first, last) = (red[0], red[red.len() - 1]);
+            assert!((first + last).abs_diff(WIDTH - 1) <= 1, "строка {}: {}..{}", y, first, last);
+        }
     }
 }
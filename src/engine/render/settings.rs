@@ -0,0 +1,26 @@
+use wgpu::Color;
+
+/// Настройки отрисовки, хранятся в ресурсах ECS
+#[derive(Clone)]
+pub struct RenderSettings {
+    pub clear_color: Color,
+    /// Вывод положения камеры в консоль каждый кадр
+    pub log_camera: bool,
+    /// Половина стороны области вокруг камеры, где направленный свет отбрасывает тени
+    pub directional_shadow_extent: f32,
+    /// Бюджет слоёв карт теней: направленный свет и прожектор занимают
+    /// по слою, точечный - шесть. Источникам сверх бюджета теней не достаётся.
+    /// Не больше `max_texture_array_layers` устройства, в wgpu это минимум 256
+    pub shadow_layers: u32
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            clear_color: Color::BLACK,
+            log_camera: false,
+            directional_shadow_extent: 50.0,
+            shadow_layers: 16
+        }
+    }
+}
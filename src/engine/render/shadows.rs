@@ -0,0 +1,225 @@
+use wgpu::*;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::StoreOp::Store;
+use crate::engine::core::primitives::*;
+use crate::engine::ecs::*;
+use crate::engine::render::renderable::*;
+
+/// Разрешение теней
+pub const SHADOW_RESOLUTION: u32 = 1024;
+
+/// Карты теней всех источников в одном массиве слоёв. Направленный свет
+/// и прожектор занимают по слою, точечный - шесть, по грани куба.
+/// Число слоёв - бюджет теней, источники сверх него остаются без теней
+pub struct ShadowMaps {
+    pub view: TextureView,
+    pub matrix_buffer: Buffer,
+    layer_views: Vec<TextureView>,
+    pass_buffers: Vec<Buffer>,
+    pass_bind_groups: Vec<BindGroup>,
+    /// Занятые в этом кадре слои
+    used: usize
+}
+
+impl ShadowMaps {
+    /// Число слоёв ограничено `max_texture_array_layers` устройства
+    pub fn new(device: &Device, pass_bind_group_layout: &BindGroupLayout, layers: u32) -> Self {
+        let layers = layers.min(device.limits().max_texture_array_layers);
+
+        // Массив из одного слоя некоторые бэкенды считают обычной текстурой
+        let allocated = layers.max(2);
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Shadow Maps Texture"),
+            size: Extent3d {
+                width: SHADOW_RESOLUTION,
+                height: SHADOW_RESOLUTION,
+                depth_or_array_layers: allocated
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Depth32Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[]
+        });
+
+        let view = texture.create_view(&TextureViewDescriptor {
+            label: Some("Shadow Maps View"),
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let layer_views = (0..layers).map(|i| {
+            texture.create_view(&TextureViewDescriptor {
+                label: Some(&format!("Shadow Layer {}", i)),
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: i,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        }).collect::<Vec<_>>();
+
+        let matrix_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Shadow Matrix Buffer"),
+            size: (size_of::<Mat4>() * allocated as usize) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        let (pass_buffers, pass_bind_groups) = (0..layers).map(|i| {
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(&format!("Shadow Layer {} Uniform Buffer", i)),
+                contents: bytemuck::bytes_of(&ShadowUniforms::default()),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+            });
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                layout: pass_bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding()
+                    }
+                ],
+                label: Some("shadow_pass_bind_group")
+            });
+
+            (buffer, bind_group)
+        }).unzip();
+
+        Self {
+            view,
+            matrix_buffer,
+            layer_views,
+            pass_buffers,
+            pass_bind_groups,
+            used: 0
+        }
+    }
+
+    /// Бюджет в слоях
+    pub fn layers(&self) -> u32 {
+        self.layer_views.len() as u32
+    }
+
+    /// Слоёв на источник
+    pub fn layers_for(light: &Light) -> u32 {
+        match light.light_type {
+            LIGHT_POINT => 6,
+            _ => 1
+        }
+    }
+
+    /// Назначение слоёв источникам по порядку, пока хватает бюджета
+    pub fn allocate(&self, lights: &mut [Light]) {
+        let mut next = 0;
+
+        for light in lights {
+            light.shadow_index = -1;
+
+            let needed = Self::layers_for(light);
+            if light.casts_shadows() && next + needed <= self.layers() {
+                light.shadow_index = next as i32;
+                next += needed;
+            }
+        }
+    }
+
+    /// Матрицы слоёв для текущих источников. Направленный свет покрывает
+    /// область вокруг камеры
+    pub fn update(&mut self, queue: &Queue, lights: &[Light], camera_pos: Vec3, directional_extent: f32) {
+        let mut matrices = vec![Mat4::IDENTITY; self.layers().max(2) as usize];
+        self.used = 0;
+
+        for light in lights.iter().filter(|light| light.shadow_index >= 0) {
+            let base = light.shadow_index as usize;
+
+            let light_matrices = match light.light_type {
+                LIGHT_POINT => point_shadow_matrices(light).to_vec(),
+                LIGHT_SPOT => vec![spot_shadow_matrix(light)],
+                _ => vec![directional_shadow_matrix(light.direction, camera_pos, directional_extent)]
+            };
+
+            for (i, matrix) in light_matrices.into_iter().enumerate() {
+                matrices[base + i] = matrix;
+                queue.write_buffer(&self.pass_buffers[base + i], 0, bytemuck::bytes_of(&ShadowUniforms { light_view_projection: matrix }));
+            }
+
+            self.used = self.used.max(base + Self::layers_for(light) as usize);
+        }
+
+        queue.write_buffer(&self.matrix_buffer, 0, bytemuck::cast_slice(&matrices));
+    }
+
+    /// Проходы теней для занятых слоёв
+    pub fn render(&self, encoder: &mut CommandEncoder, pipeline: &RenderPipeline, ecs: &ECS) {
+        for layer in 0..self.used {
+            let mut shadow_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[layer],
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: Store
+                    }),
+                    stencil_ops: None
+                }),
+                ..Default::default()
+            });
+
+            shadow_pass.set_pipeline(pipeline);
+            shadow_pass.set_bind_group(0, &self.pass_bind_groups[layer], &[]);
+
+            for (_, renderable) in ecs.query::<RenderableMesh>() {
+                shadow_pass.set_bind_group(1, &renderable.bind_group, &[]);
+                renderable.draw(&mut shadow_pass);
+            }
+        }
+    }
+}
+
+/// Ортогональная проекция для направленного света: куб со стороной
+/// `2 * extent` с центром в `center`, свет падает вдоль `direction`
+fn directional_shadow_matrix(direction: Vec3, center: Vec3, extent: f32) -> Mat4 {
+    let direction = direction.normalize();
+    let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    let eye = center - direction * (extent * 2.0);
+
+    let view = Mat4::look_at(eye, center, up);
+    let projection = Mat4::orthographic(-extent, extent, -extent, extent, 0.0, extent * 4.0);
+
+    projection * view
+}
+
+/// Перспективная проекция по внешнему конусу прожектора
+fn spot_shadow_matrix(light: &Light) -> Mat4 {
+    let direction = light.direction.normalize();
+    let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+
+    let view = Mat4::look_at(light.position, light.position + direction, up);
+    let fov = (light.outer_angle * 2.0).clamp(1.0, 170.0);
+    let projection = Mat4::perspective(fov, 1.0, 0.1, light.range.max(0.2));
+
+    projection * view
+}
+
+/// Шесть граней куба с углом обзора 90 градусов
+fn point_shadow_matrices(light: &Light) -> [Mat4; 6] {
+    let projection = Mat4::perspective(90.0, 1.0, 0.1, light.range.max(0.2));
+
+    // Порядок граней совпадает с выбором грани в шейдере
+    let faces = [
+        (Vec3::X, -Vec3::Y),
+        (-Vec3::X, -Vec3::Y),
+        (Vec3::Y, Vec3::Z),
+        (-Vec3::Y, -Vec3::Z),
+        (Vec3::Z, -Vec3::Y),
+        (-Vec3::Z, -Vec3::Y)
+    ];
+
+    faces.map(|(direction, up)| {
+        projection * Mat4::look_at(light.position, light.position + direction, up)
+    })
+}
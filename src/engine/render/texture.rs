@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::path::Path;
+use image::RgbaImage;
+use wgpu::*;
+
+/// Параметры выборки текстуры
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct SamplerOptions {
+    pub address_mode: AddressMode,
+    pub filter: FilterMode,
+    /// Цепочка уменьшенных копий для сглаживания на расстоянии
+    pub mipmaps: bool
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode: AddressMode::Repeat,
+            filter: FilterMode::Linear,
+            mipmaps: true
+        }
+    }
+}
+
+/// Что хранит текстура. От этого зависят формат и построение уровней детализации
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TextureKind {
+    /// Цвет, хранится в sRGB
+    Color,
+    /// Линейные данные вроде масок и шероховатости
+    Linear,
+    /// Карта нормалей: линейна, уровни детализации приводятся к единичной длине
+    Normal
+}
+
+impl TextureKind {
+    pub fn is_srgb(self) -> bool {
+        self == TextureKind::Color
+    }
+}
+
+/// Текстура на видеокарте вместе со своим сэмплером
+#[derive(Clone)]
+pub struct TextureAsset {
+    pub texture: wgpu::Texture,
+    pub view: TextureView,
+    pub sampler: Sampler,
+    pub width: u32,
+    pub height: u32
+}
+
+impl TextureAsset {
+    /// Загрузка изображения с диска
+    pub fn load(device: &Device, queue: &Queue, path: impl AsRef<Path>, kind: TextureKind, options: SamplerOptions) -> Result<Self, String> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .to_rgba8();
+
+        Ok(Self::from_image(device, queue, &image, &path.display().to_string(), kind, options))
+    }
+
+    /// Текстура 1x1 одного цвета
+    pub fn solid(device: &Device, queue: &Queue, color: [u8; 4], kind: TextureKind) -> Self {
+        let image = RgbaImage::from_pixel(1, 1, image::Rgba(color));
+        let options = SamplerOptions { mipmaps: false, ..SamplerOptions::default() };
+
+        Self::from_image(device, queue, &image, "Solid Texture", kind, options)
+    }
+
+    /// Уровни детализации строятся на процессоре уменьшением вдвое, см. `downsample`
+    pub fn from_image(device: &Device, queue: &Queue, image: &RgbaImage, label: &str, kind: TextureKind, options: SamplerOptions) -> Self {
+        let (width, height) = image.dimensions();
+        let mip_level_count = if options.mipmaps {
+            32 - width.max(height).max(1).leading_zeros()
+        } else {
+            1
+        };
+
+        let format = if kind.is_srgb() { TextureFormat::Rgba8UnormSrgb } else { TextureFormat::Rgba8Unorm };
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[]
+        });
+
+        let mut level = image.clone();
+        for mip_level in 0..mip_level_count {
+            if mip_level > 0 {
+                level = downsample(&level, kind);
+            }
+
+            queue.write_texture(
+                TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All
+                },
+                level.as_raw(),
+                TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level.width()),
+                    rows_per_image: Some(level.height())
+                },
+                Extent3d {
+                    width: level.width(),
+                    height: level.height(),
+                    depth_or_array_layers: 1
+                }
+            );
+        }
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: options.address_mode,
+            address_mode_v: options.address_mode,
+            address_mode_w: options.address_mode,
+            mag_filter: options.filter,
+            min_filter: options.filter,
+            mipmap_filter: options.filter,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            width,
+            height
+        }
+    }
+}
+
+/// Загруженные текстуры по пути и параметрам. Вместо отсутствующих
+/// подставляются заглушки: белый цвет и плоская нормаль
+pub struct Textures {
+    loaded: HashMap<(String, TextureKind, SamplerOptions), TextureAsset>,
+    pub white: TextureAsset,
+    pub flat_normal: TextureAsset
+}
+
+impl Textures {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        Self {
+            loaded: HashMap::new(),
+            white: TextureAsset::solid(device, queue, [255, 255, 255, 255], TextureKind::Color),
+            flat_normal: TextureAsset::solid(device, queue, [128, 128, 255, 255], TextureKind::Normal)
+        }
+    }
+
+    /// Ошибка загрузки выводится один раз, дальше используется заглушка
+    pub fn get_or_load(&mut self, device: &Device, queue: &Queue, path: &str, kind: TextureKind, options: SamplerOptions) -> TextureAsset {
+        let key = (path.to_string(), kind, options);
+
+        if let Some(texture) = self.loaded.get(&key) {
+            return texture.clone();
+        }
+
+        let texture = TextureAsset::load(device, queue, path, kind, options).unwrap_or_else(|e| {
+            eprintln!("Ошибка загрузки текстуры: {}", e);
+            match kind {
+                TextureKind::Normal => self.flat_normal.clone(),
+                TextureKind::Color | TextureKind::Linear => self.white.clone()
+            }
+        });
+
+        self.loaded.insert(key, texture.clone());
+        texture
+    }
+
+    /// Забыть загруженные текстуры, чтобы следующая сцена читала файлы заново.
+    /// Уже созданные объекты держат свои копии до удаления
+    pub fn clear(&mut self) {
+        self.loaded.clear();
+    }
+}
+
+/// Следующий уровень детализации: среднее по квадратам 2x2. Цвет усредняется
+/// в линейном пространстве, среднее нормалей снова приводится к единичной длине
+pub fn downsample(image: &RgbaImage, kind: TextureKind) -> RgbaImage {
+    let (width, height) = image.dimensions();
+
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let mut sum = [0.0f32; 4];
+
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let pixel = image.get_pixel((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1)).0;
+
+            for (channel, value) in pixel.iter().enumerate() {
+                let value = *value as f32 / 255.0;
+                let value = match (channel, kind) {
+                    (3, _) | (_, TextureKind::Linear) => value,
+                    (_, TextureKind::Color) => srgb_to_linear(value),
+                    (_, TextureKind::Normal) => value * 2.0 - 1.0
+                };
+
+                sum[channel] += value / 4.0;
+            }
+        }
+
+        let rgb = match kind {
+            TextureKind::Color => [sum[0], sum[1], sum[2]].map(linear_to_srgb),
+            TextureKind::Linear => [sum[0], sum[1], sum[2]],
+            TextureKind::Normal => {
+                let length = (sum[0] * sum[0] + sum[1] * sum[1] + sum[2] * sum[2]).sqrt();
+                let normal = if length > 1e-6 { [sum[0], sum[1], sum[2]].map(|v| v / length) } else { [0.0, 0.0, 1.0] };
+                normal.map(|v| v * 0.5 + 0.5)
+            }
+        };
+
+        image::Rgba([rgb[0], rgb[1], rgb[2], sum[3]].map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8))
+    })
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_mips_average_in_linear_space() {
+        let image = RgbaImage::from_fn(2, 2, |x, y| {
+            if (x + y) % 2 == 0 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) }
+        });
+
+        let mip = downsample(&image, TextureKind::Color);
+
+        assert_eq!(mip.dimensions(), (1, 1));
+        // Половина яркости в линейном пространстве, а не 128
+        assert_eq!(mip.get_pixel(0, 0).0, [188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn normal_map_mips_stay_unit_length() {
+        // Нормали, наклонённые в разные стороны по X
+        let image = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 { image::Rgba([218, 128, 218, 255]) } else { image::Rgba([38, 128, 218, 255]) }
+        });
+
+        let [r, g, b, _] = downsample(&image, TextureKind::Normal).get_pixel(0, 0).0;
+        let normal = [r, g, b].map(|v| v as f32 / 255.0 * 2.0 - 1.0);
+        let length = normal.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+        assert!((length - 1.0).abs() < 0.02, "длина {}", length);
+        assert!(normal[0].abs() < 0.01 && normal[2] > 0.99, "{:?}", normal);
+    }
+
+    #[test]
+    fn linear_mips_are_plain_averages() {
+        let image = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 { image::Rgba([0, 40, 255, 255]) } else { image::Rgba([255, 40, 255, 255]) }
+        });
+
+        // Без перевода из sRGB и без нормировки
+        assert_eq!(downsample(&image, TextureKind::Linear).get_pixel(0, 0).0, [128, 40, 255, 255]);
+    }
+
+    #[test]
+    fn odd_sizes_shrink_to_one_pixel() {
+        let mut image = RgbaImage::from_pixel(5, 3, image::Rgba([10, 20, 30, 40]));
+
+        while image.dimensions() != (1, 1) {
+            image = downsample(&image, TextureKind::Color);
+        }
+
+        assert_eq!(image.get_pixel(0, 0).0, [10, 20, 30, 40]);
+    }
+}
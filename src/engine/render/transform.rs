@@ -1,7 +1,7 @@
 use crate::engine::core::primitives::*;
 
 /// Позиционирование
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Transform {
     pub position: Vec3,
     pub rotation: Quat,
@@ -23,3 +23,29 @@ impl Transform {
         Self { position, rotation, scale }
     }
 }
+
+impl Transform {
+    /// Перевод дочернего локального позиционирования в систему координат этого.
+    /// Неравномерный масштаб родителя с поворотом ребёнка даёт сдвиг, он не учитывается
+    pub fn mul_transform(&self, child: &Transform) -> Transform {
+        let scaled = Vec3::new(
+            child.position.x * self.scale.x,
+            child.position.y * self.scale.y,
+            child.position.z * self.scale.z
+        );
+
+        Transform {
+            position: self.position + self.rotation * scaled,
+            rotation: (self.rotation * child.rotation).normalize(),
+            scale: Vec3::new(
+                self.scale.x * child.scale.x,
+                self.scale.y * child.scale.y,
+                self.scale.z * child.scale.z
+            )
+        }
+    }
+}
+
+/// Мировое позиционирование. Вычисляется из локальных `Transform` по иерархии
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlobalTransform(pub Transform);
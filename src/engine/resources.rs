@@ -0,0 +1,53 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Глобальные данные мира, по одному значению каждого типа
+#[derive(Default)]
+pub struct Resources {
+    values: HashMap<TypeId, Box<dyn Any>>
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Возвращает предыдущее значение того же типа
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|v| v.downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|v| v.downcast_mut())
+    }
+
+    pub fn get_or_insert_with<T: 'static>(&mut self, f: impl FnOnce() -> T) -> &mut T {
+        self.values
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(f()))
+            .downcast_mut()
+            .unwrap()
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|v| v.downcast().ok())
+            .map(|v| *v)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::engine::core::document::*;
+use crate::engine::ecs::*;
+use crate::engine::engine::*;
+use crate::engine::prefab::*;
+use crate::engine::render::camera::*;
+use crate::engine::render::material::*;
+use crate::engine::render::mesh::*;
+use crate::engine::render::renderable::*;
+use crate::engine::render::texture::*;
+use crate::engine::render::transform::*;
+use crate::engine::scene::manager::*;
+
+/// Сцена, описанная текстовым файлом:
+///
+/// ```text
+/// [camera]
+/// position = 0 1 5
+/// fov = 90
+///
+/// [entity]
+/// position = 0 -1 0
+/// scale = 100 0.1 100
+/// mesh = cube
+/// base_color = 0.5 0.5 0.5
+/// roughness = 0.9
+///
+/// [entity]
+/// id = lamp
+/// position = 0 3 0
+///
+/// [entity]
+/// rotation = -0.5 0 0 0.866
+/// light = directional
+///
+/// [entity]
+/// parent = lamp
+/// light = point
+/// color = 1 1 1
+/// intensity = 10
+///
+/// [entity]
+/// prefab = lamp_post
+/// position = 5 0 -6
+/// ```
+pub struct FileScene {
+    pub path: PathBuf
+}
+
+impl FileScene {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+}
+
+impl Scene for FileScene {
+    fn load(&mut self, engine: &mut Engine) {
+        if let Err(e) = load_file(engine, &self.path) {
+            eprintln!("Ошибка загрузки сцены: {}", e);
+        }
+    }
+}
+
+pub fn load_file(engine: &mut Engine, path: impl AsRef<Path>) -> Result<Vec<Entity>, String> {
+    let document = Document::load(path)?;
+    load(engine, &document)
+}
+
+pub fn save_file(engine: &Engine, path: impl AsRef<Path>) -> Result<(), String> {
+    save(engine).save(path)
+}
+
+/// Добавление содержимого документа в мир. Возвращает созданные сущности.
+/// Документ разбирается целиком до изменения мира: при ошибке мир не трогается
+pub fn load(engine: &mut Engine, document: &Document) -> Result<Vec<Entity>, String> {
+    let camera = document.section("camera").map(read_camera).transpose()?;
+    let descs = parse_entities(&engine.ecs, document)?;
+
+    if let Some(camera) = camera {
+        *engine.get_camera_mut() = camera;
+    }
+
+    let (entities, _) = spawn_entities(engine, &descs);
+    Ok(entities)
+}
+
+/// Создание сущностей из секций `[entity]` с восстановлением иерархии.
+/// Возвращает сущности и соответствие id из документа
+pub fn load_entities(engine: &mut Engine, document: &Document) -> Result<(Vec<Entity>, HashMap<String, Entity>), String> {
+    let descs = parse_entities(&engine.ecs, document)?;
+    Ok(spawn_entities(engine, &descs))
+}
+
+/// Разбор и проверка всех секций `[entity]`: значения ключей, шаблоны,
+/// уникальность id и иерархия. Мир не изменяется
+pub fn parse_entities(ecs: &ECS, document: &Document) -> Result<Vec<EntityDesc>, String> {
+    let prefabs = ecs.get_resource::<Prefabs>();
+    let mut descs = Vec::new();
+    let mut ids = HashMap::new();
+
+    for section in document.sections("entity") {
+        let desc = read_entity(section)?;
+
+        if let EntityContent::Prefab { name, .. } = &desc.content && !prefabs.is_some_and(|p| p.contains(name)) {
+            return Err(format!("[entity] (строка {}): нет шаблона \"{}\"", desc.line, name));
+        }
+
+        if let Some(id) = &desc.id && ids.insert(id.clone(), descs.len()).is_some() {
+            return Err(format!("[entity] (строка {}): id \"{}\" уже занят", desc.line, id));
+        }
+
+        descs.push(desc);
+    }
+
+    for desc in &descs {
+        let mut parent = desc.parent.as_ref();
+        let mut depth = 0;
+
+        while let Some(id) = parent {
+            let index = *ids
+                .get(id)
+                .ok_or_else(|| format!("[entity] (строка {}): нет сущности с id \"{}\"", desc.line, id))?;
+
+            depth += 1;
+            if depth > descs.len() {
+                return Err(format!("[entity] (строка {}): циклическая иерархия", desc.line));
+            }
+
+            parent = descs[index].parent.as_ref();
+        }
+    }
+
+    Ok(descs)
+}
+
+/// Создание проверенных `parse_entities` сущностей
+pub fn spawn_entities(engine: &mut Engine, descs: &[EntityDesc]) -> (Vec<Entity>, HashMap<String, Entity>) {
+    let mut entities = Vec::new();
+    let mut ids = HashMap::new();
+
+    for desc in descs {
+        let entity = spawn_entity(engine, desc);
+
+        if let Some(id) = &desc.id {
+            ids.insert(id.clone(), entity);
+        }
+
+        entities.push(entity);
+    }
+
+    for (desc, &child) in descs.iter().zip(&entities) {
+        if let Some(parent) = desc.parent.as_ref().and_then(|id| ids.get(id)) {
+            engine.set_parent(child, *parent);
+        }
+    }
+
+    (entities, ids)
+}
+
+/// Сохранение текущего мира в документ
+pub fn save(engine: &Engine) -> Document {
+    write_world(&engine.ecs)
+}
+
+pub fn write_world(ecs: &ECS) -> Document {
+    let mut document = Document::new();
+
+    if let Some(camera) = ecs.get_resource::<Camera>() {
+        document.push(write_camera(camera));
+    }
+
+    for entity in ecs.entities.iter() {
+        let mut section = Section::new("entity");
+
+        if !ecs.children(entity).is_empty() {
+            section.set("id", entity);
+        }
+
+        write_entity(&mut section, ecs, entity);
+
+        if !section.entries.is_empty() {
+            document.push(section);
+        }
+    }
+
+    document
+}
+
+/// Entity
+const ENTITY_KEYS: [&str; 3] = ["id", "parent", "mesh"];
+const PREFAB_INSTANCE_KEYS: [&str; 7] = ["id", "parent", "prefab", "position", "rotation", "scale", "color"];
+
+/// Разобранная секция `[entity]`
+#[derive(Clone)]
+pub struct EntityDesc {
+    pub id: Option<String>,
+    pub parent: Option<String>,
+    pub line: usize,
+    pub content: EntityContent
+}
+
+#[derive(Clone)]
+pub enum EntityContent {
+    /// Экземпляр зарегистрированного шаблона
+    Prefab {
+        name: String,
+        overrides: Overrides
+    },
+    Components {
+        transform: Transform,
+        mesh: Option<Mesh>,
+        material: Option<Material>,
+        light: Option<Light>
+    }
+}
+
+/// Сущность с позиционированием, сеткой и светом из секции.
+/// С ключом `prefab` описывается экземпляр шаблона
+pub fn read_entity(section: &Section) -> Result<EntityDesc, String> {
+    let content = match section.get("prefab") {
+        Some(name) => {
+            warn_unknown_keys(section, &[&PREFAB_INSTANCE_KEYS]);
+
+            EntityContent::Prefab {
+                name: name.to_string(),
+                overrides: read_overrides(section)?
+            }
+        }
+        None => {
+            warn_unknown_keys(section, &[&ENTITY_KEYS, &TRANSFORM_KEYS, &MATERIAL_KEYS, &LIGHT_KEYS]);
+
+            let mesh = match section.parse("mesh", parse_mesh_kind)? {
+                Some(kind) => Some(kind
+                    .build()
+                    .ok_or_else(|| format!("[entity] (строка {}): произвольные сетки не поддерживаются", section.line))?),
+                None => None
+            };
+
+            EntityContent::Components {
+                transform: read_transform(section)?,
+                mesh,
+                material: read_material(section)?,
+                light: read_light(section)?
+            }
+        }
+    };
+
+    Ok(EntityDesc {
+        id: section.get("id").map(str::to_string),
+        parent: section.get("parent").map(str::to_string),
+        line: section.line,
+        content
+    })
+}
+
+/// Родитель не назначается. Шаблон должен быть зарегистрирован, иначе
+/// создаётся пустая сущность
+pub fn spawn_entity(engine: &mut Engine, desc: &EntityDesc) -> Entity {
+    match &desc.content {
+        EntityContent::Prefab { name, overrides } => engine
+            .spawn_prefab(name, *overrides)
+            .unwrap_or_else(|| engine.create_entity()),
+        EntityContent::Components { transform, mesh, material, light } => {
+            let entity = engine.create_entity();
+            engine.add_transform(entity, transform.clone());
+
+            if let Some(mesh) = mesh {
+                engine.add_mesh(entity, mesh.clone());
+            }
+
+            if let Some(material) = material {
+                engine.add_material(entity, material.clone());
+            }
+
+            if let Some(light) = light {
+                engine.add_light(entity, *light);
+            }
+
+            entity
+        }
+    }
+}
+
+/// Ключи, которых нет ни в одном из списков, скорее всего опечатки
+pub fn warn_unknown_keys(section: &Section, known: &[&[&str]]) {
+    for (key, _) in &section.entries {
+        if !known.iter().any(|keys| keys.contains(&key.as_str())) {
+            eprintln!("[{}] (строка {}): неизвестный ключ \"{}\"", section.name, section.line, key);
+        }
+    }
+}
+
+/// Всё, кроме id. Родитель записывается по `Display` сущности
+pub fn write_entity(section: &mut Section, ecs: &ECS, entity: Entity) {
+    if let Some(parent) = ecs.parent(entity) {
+        section.set("parent", parent);
+    }
+
+    if let Some(transform) = ecs.get::<Transform>(entity) {
+        write_transform(section, transform);
+    }
+
+    if let Some(mesh) = ecs.get::<Mesh>(entity) {
+        match mesh.kind {
+            MeshKind::Custom => eprintln!("Сущность {}: произвольная сетка не сохраняется", entity),
+            kind => section.set("mesh", format_mesh_kind(kind))
+        }
+    }
+
+    if let Some(material) = ecs.get::<Material>(entity) {
+        write_material(section, material);
+    }
+
+    if let Some(light) = ecs.get::<Light>(entity) {
+        write_light(section, light);
+    }
+}
+
+/// Prefab
+pub fn read_overrides(section: &Section) -> Result<Overrides, String> {
+    Ok(Overrides {
+        position: section.get_vec3("position")?,
+        rotation: section.get_quat("rotation")?,
+        scale: section.get_vec3("scale")?,
+        color: section.get_vec3("color")?
+    })
+}
+
+/// Camera
+const CAMERA_KEYS: [&str; 5] = ["position", "rotation", "fov", "near", "far"];
+
+pub fn read_camera(section: &Section) -> Result<Camera, String> {
+    warn_unknown_keys(section, &[&CAMERA_KEYS]);
+
+    let mut camera = Camera::default();
+
+    if let Some(position) = section.get_vec3("position")? { camera.position = position; }
+    if let Some(rotation) = section.get_quat("rotation")? { camera.rotation = rotation; }
+    if let Some(fov) = section.get_f32("fov")? { camera.fov = fov; }
+    if let Some(near) = section.get_f32("near")? { camera.near = near; }
+    if let Some(far) = section.get_f32("far")? { camera.far = far; }
+
+    Ok(camera)
+}
+
+pub fn write_camera(camera: &Camera) -> Section {
+    Section::new("camera")
+        .with("position", format_vec3(camera.position))
+        .with("rotation", format_quat(camera.rotation))
+        .with("fov", camera.fov)
+        .with("near", camera.near)
+        .with("far", camera.far)
+}
+
+/// Transform
+const TRANSFORM_KEYS: [&str; 3] = ["position", "rotation", "scale"];
+
+pub fn read_transform(section: &Section) -> Result<Transform, String> {
+    let mut transform = Transform::default();
+
+    if let Some(position) = section.get_vec3("position")? { transform.position = position; }
+    if let Some(rotation) = section.get_quat("rotation")? { transform.rotation = rotation; }
+    if let Some(scale) = section.get_vec3("scale")? { transform.scale = scale; }
+
+    Ok(transform)
+}
+
+pub fn write_transform(section: &mut Section, transform: &Transform) {
+    section.set("position", format_vec3(transform.position));
+    section.set("rotation", format_quat(transform.rotation));
+    section.set("scale", format_vec3(transform.scale));
+}
+
+/// Material
+const MATERIAL_KEYS: [&str; 9] = [
+    "base_color", "emissive", "roughness", "metallic",
+    "base_color_texture", "normal_texture", "texture_wrap", "texture_filter", "mipmaps"
+];
+
+/// Материал есть у сущности, если задан хотя бы один из его ключей
+pub fn read_material(section: &Section) -> Result<Option<Material>, String> {
+    if !MATERIAL_KEYS.iter().any(|key| section.get(key).is_some()) {
+        return Ok(None);
+    }
+
+    let mut material = Material::default();
+
+    if let Some(color) = section.get_vec3("base_color")? { material.base_color = color; }
+    if let Some(emissive) = section.get_vec3("emissive")? { material.emissive = emissive; }
+    if let Some(roughness) = section.get_f32("roughness")? { material = material.with_roughness(roughness); }
+    if let Some(metallic) = section.get_f32("metallic")? { material = material.with_metallic(metallic); }
+    if let Some(path) = section.get("base_color_texture") { material.base_color_texture = Some(path.to_string()); }
+    if let Some(path) = section.get("normal_texture") { material.normal_texture = Some(path.to_string()); }
+    if let Some(mode) = section.parse("texture_wrap", parse_address_mode)? { material.sampler.address_mode = mode; }
+    if let Some(filter) = section.parse("texture_filter", parse_filter_mode)? { material.sampler.filter = filter; }
+    if let Some(mipmaps) = section.get_bool("mipmaps")? { material.sampler.mipmaps = mipmaps; }
+
+    Ok(Some(material))
+}
+
+pub fn write_material(section: &mut Section, material: &Material) {
+    section.set("base_color", format_vec3(material.base_color));
+    section.set("emissive", format_vec3(material.emissive));
+    section.set("roughness", material.roughness);
+    section.set("metallic", material.metallic);
+
+    if let Some(path) = &material.base_color_texture {
+        section.set("base_color_texture", path);
+    }
+
+    if let Some(path) = &material.normal_texture {
+        section.set("normal_texture", path);
+    }
+
+    if material.sampler != SamplerOptions::default() {
+        section.set("texture_wrap", format_address_mode(material.sampler.address_mode));
+        section.set("texture_filter", format_filter_mode(material.sampler.filter));
+        section.set("mipmaps", material.sampler.mipmaps);
+    }
+}
+
+pub fn parse_address_mode(value: &str) -> Result<wgpu::AddressMode, String> {
+    match value.trim() {
+        "repeat" => Ok(wgpu::AddressMode::Repeat),
+        "mirror" => Ok(wgpu::AddressMode::MirrorRepeat),
+        "clamp" => Ok(wgpu::AddressMode::ClampToEdge),
+        other => Err(format!("неизвестный режим повторения \"{}\"", other))
+    }
+}
+
+pub fn format_address_mode(mode: wgpu::AddressMode) -> String {
+    match mode {
+        wgpu::AddressMode::MirrorRepeat => "mirror".to_string(),
+        wgpu::AddressMode::ClampToEdge | wgpu::AddressMode::ClampToBorder => "clamp".to_string(),
+        wgpu::AddressMode::Repeat => "repeat".to_string()
+    }
+}
+
+pub fn parse_filter_mode(value: &str) -> Result<wgpu::FilterMode, String> {
+    match value.trim() {
+        "linear" => Ok(wgpu::FilterMode::Linear),
+        "nearest" => Ok(wgpu::FilterMode::Nearest),
+        other => Err(format!("неизвестная фильтрация \"{}\"", other))
+    }
+}
+
+pub fn format_filter_mode(filter: wgpu::FilterMode) -> String {
+    match filter {
+        wgpu::FilterMode::Linear => "linear".to_string(),
+        wgpu::FilterMode::Nearest => "nearest".to_string()
+    }
+}
+
+/// Light
+const LIGHT_KEYS: [&str; 7] = ["light", "color", "intensity", "range", "inner_angle", "outer_angle", "shadows"];
+
+pub fn read_light(section: &Section) -> Result<Option<Light>, String> {
+    let Some(light_type) = section.parse("light", parse_light_type)? else {
+        return Ok(None);
+    };
+
+    let mut light = Light { light_type, ..Light::default() };
+
+    if let Some(color) = section.get_vec3("color")? { light.color = color; }
+    if let Some(intensity) = section.get_f32("intensity")? { light.intensity = intensity; }
+    if let Some(range) = section.get_f32("range")? { light.range = range; }
+    if let Some(shadows) = section.get_bool("shadows")? { light = light.with_shadows(shadows); }
+
+    let inner = section.get_f32("inner_angle")?.unwrap_or(light.inner_angle);
+    let outer = section.get_f32("outer_angle")?.unwrap_or(light.outer_angle);
+
+    if !(0.0 <= inner && inner <= outer && outer <= MAX_SPOT_ANGLE) {
+        return Err(format!(
+            "[{}] (строка {}): нужно 0 <= inner_angle <= outer_angle <= {}, указано {} и {}",
+            section.name, section.line, MAX_SPOT_ANGLE, inner, outer
+        ));
+    }
+
+    light.inner_angle = inner;
+    light.outer_angle = outer;
+
+    Ok(Some(light))
+}
+
+pub fn write_light(section: &mut Section, light: &Light) {
+    section.set("light", format_light_type(light.light_type));
+    section.set("color", format_vec3(light.color));
+    section.set("intensity", light.intensity);
+    section.set("range", light.range);
+
+    if light.light_type == LIGHT_SPOT {
+        section.set("inner_angle", light.inner_angle);
+        section.set("outer_angle", light.outer_angle);
+    }
+
+    if !light.casts_shadows() {
+        section.set("shadows", false);
+    }
+}
+
+pub fn parse_light_type(value: &str) -> Result<u32, String> {
+    match value.trim() {
+        "point" => Ok(LIGHT_POINT),
+        "directional" => Ok(LIGHT_DIRECTIONAL),
+        "spot" => Ok(LIGHT_SPOT),
+        other => parse_u32(other).map_err(|_| format!("неизвестный тип освещения \"{}\"", other))
+    }
+}
+
+pub fn format_light_type(light_type: u32) -> String {
+    match light_type {
+        LIGHT_POINT => "point".to_string(),
+        LIGHT_DIRECTIONAL => "directional".to_string(),
+        LIGHT_SPOT => "spot".to_string(),
+        other => other.to_string()
+    }
+}
+
+/// Mesh
+pub fn parse_mesh_kind(value: &str) -> Result<MeshKind, String> {
+    let mut parts = value.split_whitespace();
+    let name = parts.next().unwrap_or_default();
+    let segments = match parts.next() {
+        Some(segments) => segments
+            .parse::<u16>()
+            .map_err(|_| format!("\"{}\" не число сегментов", segments))?,
+        None => 32
+    };
+
+    match name {
+        "cube" => Ok(MeshKind::Cube),
+        "cone" => Ok(MeshKind::Cone(segments)),
+        "cylinder" => Ok(MeshKind::Cylinder(segments)),
+        "sphere" => Ok(MeshKind::Sphere(segments)),
+        other => Err(format!("неизвестная сетка \"{}\"", other))
+    }
+}
+
+pub fn format_mesh_kind(kind: MeshKind) -> String {
+    match kind {
+        MeshKind::Custom => "custom".to_string(),
+        MeshKind::Cube => "cube".to_string(),
+        MeshKind::Cone(segments) => format!("cone {}", segments),
+        MeshKind::Cylinder(segments) => format!("cylinder {}", segments),
+        MeshKind::Sphere(segments) => format!("sphere {}", segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::core::primitives::*;
+
+    fn parse(ecs: &ECS, text: &str) -> Result<Vec<EntityDesc>, String> {
+        parse_entities(ecs, &Document::parse(text)?)
+    }
+
+    fn components(desc: &EntityDesc) -> (&Transform, &Option<Mesh>, &Option<Material>, &Option<Light>) {
+        match &desc.content {
+            EntityContent::Components { transform, mesh, material, light } => (transform, mesh, material, light),
+            EntityContent::Prefab { name, .. } => panic!("ожидались компоненты, а не шаблон {}", name)
+        }
+    }
+
+    #[test]
+    fn world_round_trips_through_text() {
+        let mut ecs = ECS::new();
+        *ecs.resource_mut::<Camera>() = Camera::new(Vec3::new(1.0, 2.0, 3.0), Quat::IDENTITY, 70.0, 0.5, 250.0);
+
+        let parent = ecs.create_entity();
+        let parent_transform = Transform::new(Vec3::new(0.0, 3.0, 0.0), Quat::IDENTITY, Vec3::new(1.0, 2.0, 1.0));
+        let material = Material::new(Vec3::new(0.2, 0.4, 0.8))
+            .with_emissive(Vec3::new(0.0, 0.0, 0.5))
+            .with_roughness(0.1)
+            .with_metallic(1.0)
+            .with_base_color_texture("assets/textures/checker.png")
+            .with_normal_texture("assets/textures/bumps_normal.png")
+            .with_sampler(SamplerOptions { address_mode: wgpu::AddressMode::ClampToEdge, filter: wgpu::FilterMode::Nearest, mipmaps: false });
+        ecs.add_transform(parent, parent_transform.clone());
+        ecs.add_mesh(parent, Mesh::sphere(16));
+        ecs.add_material(parent, material.clone());
+
+        let child = ecs.create_entity();
+        let light = Light::spot(Vec3::new(1.0, 0.5, 0.25), 4.0, 30.0, 10.0, 25.0).with_shadows(false);
+        ecs.add_transform(child, Transform::default());
+        ecs.add_light(child, light);
+        ecs.set_parent(child, parent);
+
+        let text = write_world(&ecs).to_string();
+        let document = Document::parse(&text).unwrap();
+
+        let camera = read_camera(document.section("camera").unwrap()).unwrap();
+        assert_eq!(camera.position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!((camera.fov, camera.near, camera.far), (70.0, 0.5, 250.0));
+
+        let descs = parse_entities(&ECS::new(), &document).unwrap();
+        assert_eq!(descs.len(), 2);
+
+        let (transform, mesh, read_material, read_light) = components(&descs[0]);
+        assert_eq!(transform, &parent_transform);
+        assert_eq!(mesh.as_ref().map(|m| m.kind), Some(MeshKind::Sphere(16)));
+        assert_eq!(read_material.as_ref(), Some(&material));
+        assert!(read_light.is_none());
+
+        let (_, mesh, read_material, read_light) = components(&descs[1]);
+        let read_light = read_light.unwrap();
+        assert!(mesh.is_none() && read_material.is_none());
+        assert_eq!(descs[1].parent, descs[0].id);
+        assert_eq!(read_light.light_type, LIGHT_SPOT);
+        assert_eq!(read_light.color, light.color);
+        assert_eq!((read_light.intensity, read_light.range), (4.0, 30.0));
+        assert_eq!((read_light.inner_angle, read_light.outer_angle), (10.0, 25.0));
+        assert!(!read_light.casts_shadows());
+    }
+
+    #[test]
+    fn material_defaults_are_not_written() {
+        let mut section = Section::new("entity");
+        write_material(&mut section, &Material::default());
+
+        assert!(section.get("texture_wrap").is_none());
+        assert!(section.get("base_color_texture").is_none());
+        assert_eq!(read_material(&section).unwrap(), Some(Material::default()));
+    }
+
+    #[test]
+    fn hierarchy_errors_are_found_before_spawning() {
+        let ecs = ECS::new();
+
+        let error = parse(&ecs, "[entity]\nid = a\n\n[entity]\nid = a\n").err().unwrap();
+        assert!(error.contains("строка 4") && error.contains("\"a\""), "{}", error);
+
+        let error = parse(&ecs, "[entity]\nparent = missing\n").err().unwrap();
+        assert!(error.contains("строка 1") && error.contains("missing"), "{}", error);
+
+        let error = parse(&ecs, "[entity]\nid = a\nparent = b\n\n[entity]\nid = b\nparent = a\n").err().unwrap();
+        assert!(error.contains("циклическая иерархия"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_values_reject_the_whole_document() {
+        let ecs = ECS::new();
+
+        assert!(parse(&ecs, "[entity]\nmesh = cube\n\n[entity]\nmesh = teapot\n").err().unwrap().contains("teapot"));
+        assert!(parse(&ecs, "[entity]\nmesh = custom\n").is_err());
+        assert!(parse(&ecs, "[entity]\nlight = spot\ninner_angle = 40\nouter_angle = 30\n").is_err());
+        assert!(parse(&ecs, "[entity]\nlight = spot\nouter_angle = 95\n").is_err());
+        assert!(parse(&ecs, "[entity]\ntexture_wrap = sideways\n").is_err());
+    }
+
+    #[test]
+    fn prefab_instances_need_a_registered_prefab() {
+        let mut ecs = ECS::new();
+        let text = "[entity]\nprefab = lamp_post\nposition = 1 0 2\ncolor = 1 0 0\n";
+
+        assert!(parse(&ecs, text).err().unwrap().contains("lamp_post"));
+
+        ecs.insert_resource(Prefabs::default());
+        ecs.resource_mut::<Prefabs>().register(Prefab::new("lamp_post"));
+
+        let descs = parse(&ecs, text).unwrap();
+        let EntityContent::Prefab { name, overrides } = &descs[0].content else {
+            panic!("ожидался шаблон");
+        };
+        assert_eq!(name, "lamp_post");
+        assert_eq!(overrides.position, Some(Vec3::new(1.0, 0.0, 2.0)));
+        assert_eq!(overrides.color, Some(Vec3::X));
+        assert!(overrides.scale.is_none());
+    }
+
+    #[test]
+    fn demo_scene_parses() {
+        let mut ecs = ECS::new();
+        let mut prefabs = Prefabs::default();
+        prefabs.register(Prefab::load("assets/prefabs/lamp_post.prefab").unwrap());
+        ecs.insert_resource(prefabs);
+
+        let document = Document::load("assets/scenes/demo.scene").unwrap();
+        assert!(document.section("camera").map(read_camera).transpose().is_ok());
+        assert!(!parse_entities(&ecs, &document).unwrap().is_empty());
+    }
+}
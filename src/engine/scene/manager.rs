@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use crate::engine::ecs::*;
+use crate::engine::engine::*;
+
+/// Сцена
+pub trait Scene {
+    /// Наполнение мира сущностями
+    fn load(&mut self, engine: &mut Engine);
+
+    /// Вызывается после загрузки сцены
+    fn on_enter(&mut self, _engine: &mut Engine) {}
+
+    /// Вызывается после восстановления снимка этой сцены, перед `on_enter`.
+    /// Сущности пересозданы, `entities` сопоставляет прежние идентификаторы новым
+    fn on_restore(&mut self, _engine: &mut Engine, _entities: &HashMap<Entity, Entity>) {}
+
+    /// Вызывается перед выгрузкой сцены
+    fn on_exit(&mut self, _engine: &mut Engine) {}
+}
+
+impl<F: FnMut(&mut Engine)> Scene for F {
+    fn load(&mut self, engine: &mut Engine) {
+        self(engine)
+    }
+}
+
+/// Менеджер сцен
+#[derive(Default)]
+pub struct SceneManager {
+    scenes: HashMap<String, Box<dyn Scene>>,
+    current: Option<String>,
+    requested: Option<String>
+}
+
+impl SceneManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, name: &str, scene: impl Scene + 'static) {
+        self.scenes.insert(name.to_string(), Box::new(scene));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.scenes.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.scenes.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// Запрос на смену сцены. Применяется в начале следующего обновления
+    pub fn request(&mut self, name: &str) {
+        self.requested = Some(name.to_string());
+    }
+
+    pub fn take_request(&mut self) -> Option<String> {
+        self.requested.take()
+    }
+
+    pub(crate) fn take(&mut self, name: &str) -> Option<Box<dyn Scene>> {
+        self.scenes.remove(name)
+    }
+
+    pub(crate) fn put_back(&mut self, name: &str, scene: Box<dyn Scene>) {
+        self.scenes.insert(name.to_string(), scene);
+    }
+
+    pub(crate) fn set_current(&mut self, name: Option<&str>) {
+        self.current = name.map(str::to_string);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod manager;
+pub mod file;
+pub mod snapshot;
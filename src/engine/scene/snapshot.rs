@@ -0,0 +1,319 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::engine::core::document::*;
+use crate::engine::ecs::*;
+use crate::engine::engine::*;
+use crate::engine::render::camera::*;
+use crate::engine::scene::file::*;
+use crate::engine::script::*;
+
+/// Версия формата снимка. Увеличивать при несовместимых изменениях
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Компонент или скрипт, который попадает в снимки мира. Регистрируется через
+/// `Engine::register_persistent` или `Engine::register_persistent_script`,
+/// остальные пользовательские компоненты и скрипты пропускаются
+pub trait Persistent: Sized + 'static {
+    /// Имя типа в снимке, должно быть уникальным
+    const NAME: &'static str;
+
+    fn save(&self, section: &mut Section);
+    fn load(section: &Section) -> Result<Self, String>;
+}
+
+/// Разобранный компонент, который ещё не добавлен сущности
+type InsertFn = Box<dyn FnOnce(&mut ECS, Entity)>;
+
+#[derive(Clone, Copy)]
+struct PersistentType {
+    name: &'static str,
+    save: fn(&ECS, Entity) -> Option<Section>,
+    load: fn(&Section) -> Result<InsertFn, String>
+}
+
+#[derive(Clone, Copy)]
+struct PersistentScriptType {
+    name: &'static str,
+    save: fn(&ScriptSlot, Entity) -> Option<Section>,
+    load: fn(&Section) -> Result<Box<dyn Script>, String>
+}
+
+/// Зарегистрированные сохраняемые компоненты и скрипты, хранятся в ресурсах ECS
+#[derive(Clone, Default)]
+pub struct PersistentComponents {
+    types: Vec<PersistentType>,
+    scripts: Vec<PersistentScriptType>
+}
+
+impl PersistentComponents {
+    pub fn register<T: Persistent>(&mut self) {
+        if self.types.iter().any(|t| t.name == T::NAME) {
+            return;
+        }
+
+        self.types.push(PersistentType {
+            name: T::NAME,
+            save: |ecs, entity| {
+                let component = ecs.get::<T>(entity)?;
+                let mut section = Section::new("component")
+                    .with("entity", entity)
+                    .with("type", T::NAME);
+                component.save(&mut section);
+                Some(section)
+            },
+            load: |section| {
+                let component = T::load(section)?;
+                Ok(Box::new(move |ecs, entity| ecs.insert(entity, component)))
+            }
+        });
+    }
+
+    pub fn register_script<T: Persistent + Script>(&mut self) {
+        if self.scripts.iter().any(|t| t.name == T::NAME) {
+            return;
+        }
+
+        self.scripts.push(PersistentScriptType {
+            name: T::NAME,
+            save: |slot, entity| {
+                let script = (slot.script.as_ref() as &dyn Any).downcast_ref::<T>()?;
+                let mut section = Section::new("script")
+                    .with("entity", entity)
+                    .with("type", T::NAME)
+                    .with("started", slot.started);
+                script.save(&mut section);
+                Some(section)
+            },
+            load: |section| Ok(Box::new(T::load(section)?))
+        });
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.types.iter().map(|t| t.name)
+    }
+}
+
+/// Снимок всего мира:
+///
+/// ```text
+/// [snapshot]
+/// version = 1
+/// scene = _1
+///
+/// [camera]
+/// ...
+///
+/// [entity]
+/// id = 0v0
+/// position = 0 1 0
+/// mesh = sphere 32
+///
+/// [component]
+/// entity = 0v0
+/// type = health
+/// value = 10
+///
+/// [script]
+/// entity = 0v0
+/// type = bob
+/// started = true
+/// phase = 1.5
+/// ```
+///
+/// Незарегистрированные скрипты не сохраняются, о каждом выводится предупреждение
+pub fn capture(engine: &Engine) -> Document {
+    let ecs = &engine.ecs;
+    let mut document = Document::new();
+
+    let mut header = Section::new("snapshot").with("version", SNAPSHOT_VERSION);
+    if let Some(scene) = engine.scenes.current() {
+        header.set("scene", scene);
+    }
+    document.push(header);
+
+    if let Some(camera) = ecs.get_resource::<Camera>() {
+        document.push(write_camera(camera));
+    }
+
+    for entity in ecs.entities.iter() {
+        let mut section = Section::new("entity").with("id", entity);
+        write_entity(&mut section, ecs, entity);
+        document.push(section);
+    }
+
+    let persistent = ecs.get_resource::<PersistentComponents>().cloned().unwrap_or_default();
+    for entity in ecs.entities.iter() {
+        for persistent_type in &persistent.types {
+            if let Some(section) = (persistent_type.save)(ecs, entity) {
+                document.push(section);
+            }
+        }
+    }
+
+    let mut entities = ecs.scripts.keys().copied().collect::<Vec<_>>();
+    entities.sort();
+
+    for entity in entities {
+        for slot in &ecs.scripts[&entity] {
+            let section = persistent.scripts
+                .iter()
+                .find_map(|script_type| (script_type.save)(slot, entity));
+
+            match section {
+                Some(section) => document.push(section),
+                None => eprintln!("Скрипт сущности {} не зарегистрирован и не попадёт в снимок", entity)
+            }
+        }
+    }
+
+    document
+}
+
+/// Замена текущего мира снимком. Возвращает созданные сущности.
+/// Снимок разбирается целиком до выгрузки сцены: при ошибке мир остаётся прежним.
+/// Сущности создаются заново с новыми идентификаторами, поэтому у сцены из снимка
+/// вызывается `on_restore` с соответствием старых сущностей новым, затем `on_enter`.
+/// `load` не вызывается
+pub fn restore(engine: &mut Engine, document: &Document) -> Result<Vec<Entity>, String> {
+    let header = document
+        .section("snapshot")
+        .ok_or("нет секции [snapshot]")?;
+    let version = header
+        .parse("version", parse_u32)?
+        .ok_or("[snapshot]: не указана версия")?;
+
+    if version > SNAPSHOT_VERSION {
+        return Err(format!("версия снимка {} новее поддерживаемой {}", version, SNAPSHOT_VERSION));
+    }
+
+    let camera = document.section("camera").map(read_camera).transpose()?;
+    let descs = parse_entities(&engine.ecs, document)?;
+
+    let persistent = engine.ecs.get_resource::<PersistentComponents>().cloned().unwrap_or_default();
+    let mut components = Vec::new();
+
+    for section in document.sections("component") {
+        let id = section.get("entity").unwrap_or_default();
+        if !descs.iter().any(|desc| desc.id.as_deref() == Some(id)) {
+            return Err(format!("[component] (строка {}): нет сущности с id \"{}\"", section.line, id));
+        }
+
+        let name = section.get("type").unwrap_or_default();
+        match persistent.types.iter().find(|t| t.name == name) {
+            Some(persistent_type) => {
+                let insert = (persistent_type.load)(section)
+                    .map_err(|e| format!("[component] (строка {}): {}", section.line, e))?;
+                components.push((id, insert));
+            }
+            None => eprintln!("[component] (строка {}): тип \"{}\" не зарегистрирован", section.line, name)
+        }
+    }
+
+    let mut scripts = Vec::new();
+
+    for section in document.sections("script") {
+        let id = section.get("entity").unwrap_or_default();
+        if !descs.iter().any(|desc| desc.id.as_deref() == Some(id)) {
+            return Err(format!("[script] (строка {}): нет сущности с id \"{}\"", section.line, id));
+        }
+
+        let name = section.get("type").unwrap_or_default();
+        match persistent.scripts.iter().find(|t| t.name == name) {
+            Some(script_type) => {
+                let script = (script_type.load)(section)
+                    .map_err(|e| format!("[script] (строка {}): {}", section.line, e))?;
+                let started = section.get_bool("started")?.unwrap_or(false);
+                scripts.push((id, ScriptSlot { script, started }));
+            }
+            None => eprintln!("[script] (строка {}): скрипт \"{}\" не зарегистрирован", section.line, name)
+        }
+    }
+
+    engine.unload_scene();
+
+    if let Some(camera) = camera {
+        *engine.get_camera_mut() = camera;
+    }
+
+    let (entities, ids) = spawn_entities(engine, &descs);
+
+    for (id, insert) in components {
+        insert(&mut engine.ecs, ids[id]);
+    }
+
+    for (id, slot) in scripts {
+        engine.ecs.scripts.entry(ids[id]).or_default().push(slot);
+    }
+
+    // id сущностей в снимке - их прежние идентификаторы
+    let remap = ids
+        .iter()
+        .filter_map(|(id, entity)| Some((id.parse::<Entity>().ok()?, *entity)))
+        .collect::<HashMap<_, _>>();
+
+    let scene = header.get("scene");
+    engine.scenes.set_current(scene);
+
+    if let Some(name) = scene && let Some(mut scene) = engine.scenes.take(name) {
+        scene.on_restore(engine, &remap);
+        scene.on_enter(engine);
+        engine.scenes.put_back(name, scene);
+    }
+
+    Ok(entities)
+}
+
+pub fn save_file(engine: &Engine, path: impl AsRef<Path>) -> Result<(), String> {
+    capture(engine).save(path)
+}
+
+pub fn load_file(engine: &mut Engine, path: impl AsRef<Path>) -> Result<Vec<Entity>, String> {
+    let document = Document::load(path)?;
+    restore(engine, &document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter(u32);
+
+    impl Script for Counter {}
+
+    impl Persistent for Counter {
+        const NAME: &'static str = "counter";
+
+        fn save(&self, section: &mut Section) {
+            section.set("count", self.0);
+        }
+
+        fn load(section: &Section) -> Result<Self, String> {
+            Ok(Self(section.parse("count", parse_u32)?.unwrap_or_default()))
+        }
+    }
+
+    struct Unregistered;
+
+    impl Script for Unregistered {}
+
+    #[test]
+    fn registered_scripts_round_trip() {
+        let mut persistent = PersistentComponents::default();
+        persistent.register_script::<Counter>();
+
+        let entity = Entities::default().create();
+        let slot = ScriptSlot { script: Box::new(Counter(7)), started: true };
+        let section = (persistent.scripts[0].save)(&slot, entity).unwrap();
+
+        assert_eq!(section.get("entity"), Some("0v0"));
+        assert_eq!(section.get("type"), Some("counter"));
+        assert_eq!(section.get_bool("started"), Ok(Some(true)));
+
+        let script = (persistent.scripts[0].load)(&section).unwrap();
+        assert_eq!((script.as_ref() as &dyn Any).downcast_ref::<Counter>().map(|c| c.0), Some(7));
+
+        let other = ScriptSlot::new(Box::new(Unregistered));
+        assert!((persistent.scripts[0].save)(&other, entity).is_none());
+    }
+}
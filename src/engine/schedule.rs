@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use crate::engine::engine::*;
+
+/// Стадии кадра в порядке выполнения
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// Реакция на ввод
+    Input,
+    /// Подготовка к обновлению
+    PreUpdate,
+    /// Симуляция с фиксированным шагом, может выполняться несколько раз за кадр или ни разу
+    FixedUpdate,
+    /// Игровая логика с переменным шагом
+    Update,
+    /// Следствия обновления: иерархия, камера
+    PostUpdate,
+    /// Подготовка данных для отрисовки
+    RenderPrep
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Input,
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::RenderPrep
+    ];
+}
+
+/// Имена встроенных систем, для ограничений порядка
+pub const WINDOW_RESIZE_SYSTEM: &str = "window_resize";
+pub const SCRIPTS_FIXED_SYSTEM: &str = "scripts_fixed";
+pub const SCRIPTS_SYSTEM: &str = "scripts";
+pub const TRANSFORM_PROPAGATION_SYSTEM: &str = "transform_propagation";
+pub const CAMERA_CONTROLLER_SYSTEM: &str = "camera_controller";
+
+/// Система: именованная функция над движком
+pub struct System {
+    pub name: String,
+    pub stage: Stage,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    run: Box<dyn FnMut(&mut Engine)>
+}
+
+impl System {
+    pub fn new(name: &str, run: impl FnMut(&mut Engine) + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            stage: Stage::Update,
+            before: Vec::new(),
+            after: Vec::new(),
+            run: Box::new(run)
+        }
+    }
+
+    pub fn in_stage(mut self, stage: Stage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// Выполнять раньше системы `name` той же стадии
+    pub fn before(mut self, name: &str) -> Self {
+        self.before.push(name.to_string());
+        self
+    }
+
+    /// Выполнять позже системы `name` той же стадии
+    pub fn after(mut self, name: &str) -> Self {
+        self.after.push(name.to_string());
+        self
+    }
+
+    pub fn run(&mut self, engine: &mut Engine) {
+        (self.run)(engine);
+    }
+}
+
+/// Имя и ограничения порядка системы
+type Constraints = (String, Vec<String>, Vec<String>);
+
+/// Расписание систем по стадиям
+#[derive(Default)]
+pub struct Schedule {
+    stages: HashMap<Stage, Vec<System>>,
+    sorted: HashMap<Stage, bool>,
+    running: Option<Stage>,
+    /// Ограничения вынутых на время выполнения систем
+    taken: Vec<Constraints>,
+    removed: Vec<String>
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавление системы. Система с тем же именем заменяется.
+    /// Система, ограничения которой замыкают цикл, отклоняется
+    pub fn add(&mut self, system: System) -> Result<(), String> {
+        let stage = system.stage;
+        let mut nodes = self.stages
+            .get(&stage)
+            .into_iter()
+            .flatten()
+            .map(|s| (s.name.as_str(), s.before.as_slice(), s.after.as_slice()))
+            .collect::<Vec<_>>();
+
+        if self.running == Some(stage) {
+            nodes.extend(self.taken.iter().map(|(name, before, after)| (name.as_str(), before.as_slice(), after.as_slice())));
+        }
+
+        nodes.retain(|(name, _, _)| *name != system.name);
+        nodes.push((system.name.as_str(), system.before.as_slice(), system.after.as_slice()));
+
+        if let Err(cycle) = order(&nodes) {
+            return Err(format!("Циклические ограничения порядка систем в {:?}: {}", stage, cycle.join(", ")));
+        }
+
+        self.remove(&system.name);
+
+        self.sorted.insert(stage, false);
+        self.stages.entry(stage).or_default().push(system);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<System> {
+        for (stage, systems) in &mut self.stages {
+            if let Some(i) = systems.iter().position(|s| s.name == name) {
+                self.sorted.insert(*stage, false);
+                return Some(systems.remove(i));
+            }
+        }
+
+        // Система может быть вынута на время выполнения стадии
+        if self.running.is_some() {
+            self.removed.push(name.to_string());
+        }
+
+        None
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.stages.values().flatten().any(|s| s.name == name)
+    }
+
+    /// Имена систем стадии в порядке выполнения
+    pub fn names(&mut self, stage: Stage) -> Vec<String> {
+        self.sort(stage);
+        self.stages
+            .get(&stage)
+            .map(|systems| systems.iter().map(|s| s.name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Системы стадии вынимаются на время выполнения
+    pub(crate) fn take(&mut self, stage: Stage) -> Vec<System> {
+        self.sort(stage);
+        self.running = Some(stage);
+
+        let systems = self.stages.remove(&stage).unwrap_or_default();
+        self.taken = systems
+            .iter()
+            .map(|s| (s.name.clone(), s.before.clone(), s.after.clone()))
+            .collect();
+        systems
+    }
+
+    /// Возврат вынутых систем. Добавленные во время выполнения идут следом,
+    /// удалённые во время выполнения не возвращаются
+    pub(crate) fn put_back(&mut self, stage: Stage, mut systems: Vec<System>) {
+        let removed = std::mem::take(&mut self.removed);
+        systems.retain(|s| !removed.contains(&s.name));
+        self.running = None;
+        self.taken.clear();
+
+        if let Some(added) = self.stages.remove(&stage) {
+            systems.retain(|s| !added.iter().any(|a| a.name == s.name));
+            systems.extend(added);
+            self.sorted.insert(stage, false);
+        }
+
+        self.stages.insert(stage, systems);
+    }
+
+    /// Топологическая сортировка по ограничениям, при прочих равных - порядок добавления
+    fn sort(&mut self, stage: Stage) {
+        if self.sorted.get(&stage).copied().unwrap_or(true) {
+            return;
+        }
+
+        self.sorted.insert(stage, true);
+
+        let Some(systems) = self.stages.remove(&stage) else {
+            return;
+        };
+
+        let nodes = systems
+            .iter()
+            .map(|s| (s.name.as_str(), s.before.as_slice(), s.after.as_slice()))
+            .collect::<Vec<_>>();
+
+        // Циклы отсекаются при добавлении
+        let order = order(&nodes).unwrap_or_else(|_| (0..nodes.len()).collect());
+
+        let mut slots = systems.into_iter().map(Some).collect::<Vec<_>>();
+        let sorted = order.into_iter().map(|i| slots[i].take().unwrap()).collect();
+
+        self.stages.insert(stage, sorted);
+    }
+}
+
+/// Порядок выполнения систем, заданных именем и ограничениями `before` и `after`.
+/// При цикле возвращаются имена систем, которые не удалось упорядочить
+fn order<'a>(nodes: &[(&'a str, &[String], &[String])]) -> Result<Vec<usize>, Vec<&'a str>> {
+    let count = nodes.len();
+    let index = |name: &str| nodes.iter().position(|(n, _, _)| *n == name);
+
+    let mut edges = vec![Vec::new(); count];
+    let mut incoming = vec![0; count];
+
+    for (i, (_, before, after)) in nodes.iter().enumerate() {
+        for name in *before {
+            if let Some(j) = index(name) {
+                edges[i].push(j);
+                incoming[j] += 1;
+            }
+        }
+
+        for name in *after {
+            if let Some(j) = index(name) {
+                edges[j].push(i);
+                incoming[i] += 1;
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(count);
+    let mut done = vec![false; count];
+
+    while order.len() < count {
+        let Some(next) = (0..count).find(|i| !done[*i] && incoming[*i] == 0) else {
+            return Err((0..count).filter(|i| !done[*i]).map(|i| nodes[i].0).collect());
+        };
+
+        done[next] = true;
+        order.push(next);
+
+        for j in &edges[next] {
+            incoming[*j] -= 1;
+        }
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(name: &str) -> System {
+        System::new(name, |_| {})
+    }
+
+    #[test]
+    fn unconstrained_systems_keep_insertion_order() {
+        let mut schedule = Schedule::new();
+        schedule.add(system("a")).unwrap();
+        schedule.add(system("b")).unwrap();
+        schedule.add(system("c")).unwrap();
+
+        assert_eq!(schedule.names(Stage::Update), ["a", "b", "c"]);
+        assert!(schedule.names(Stage::PostUpdate).is_empty());
+    }
+
+    #[test]
+    fn constraints_reorder_systems() {
+        let mut schedule = Schedule::new();
+        schedule.add(system("render").after("physics")).unwrap();
+        schedule.add(system("physics")).unwrap();
+        schedule.add(system("input").before("physics")).unwrap();
+        // Ограничения на системы других стадий и несуществующие игнорируются
+        schedule.add(system("late").in_stage(Stage::PostUpdate).before("input")).unwrap();
+        schedule.add(system("ai").after("missing")).unwrap();
+
+        assert_eq!(schedule.names(Stage::Update), ["input", "physics", "render", "ai"]);
+        assert_eq!(schedule.names(Stage::PostUpdate), ["late"]);
+    }
+
+    #[test]
+    fn cycle_is_rejected_and_schedule_kept() {
+        let mut schedule = Schedule::new();
+        schedule.add(system("a").before("b")).unwrap();
+        schedule.add(system("b").before("c")).unwrap();
+
+        let error = schedule.add(system("c").before("a")).unwrap_err();
+        assert!(error.ends_with("a, b, c"), "{}", error);
+        assert!(!schedule.contains("c"));
+
+        // Замена той же системы не считается циклом с её старой версией
+        schedule.add(system("b").before("c").after("a")).unwrap();
+        assert_eq!(schedule.names(Stage::Update), ["a", "b"]);
+    }
+
+    #[test]
+    fn rejected_replacement_keeps_old_system() {
+        let mut schedule = Schedule::new();
+        schedule.add(system("a")).unwrap();
+        schedule.add(system("b").after("a")).unwrap();
+
+        assert!(schedule.add(system("a").after("b")).is_err());
+        assert_eq!(schedule.names(Stage::Update), ["a", "b"]);
+    }
+
+    #[test]
+    fn cycle_with_running_stage_is_rejected() {
+        let mut schedule = Schedule::new();
+        schedule.add(system("a").before("b")).unwrap();
+        schedule.add(system("b")).unwrap();
+
+        let systems = schedule.take(Stage::Update);
+        assert!(schedule.add(system("c").after("b").before("a")).is_err());
+        schedule.add(system("d").after("b")).unwrap();
+        schedule.put_back(Stage::Update, systems);
+
+        assert_eq!(schedule.names(Stage::Update), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn removed_while_running_is_not_returned() {
+        let mut schedule = Schedule::new();
+        schedule.add(system("a")).unwrap();
+        schedule.add(system("b")).unwrap();
+
+        let systems = schedule.take(Stage::Update);
+        schedule.remove("a");
+        schedule.put_back(Stage::Update, systems);
+
+        assert_eq!(schedule.names(Stage::Update), ["b"]);
+    }
+}
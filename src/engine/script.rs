@@ -0,0 +1,33 @@
+use std::any::Any;
+use crate::engine::ecs::*;
+use crate::engine::engine::*;
+
+/// Поведение сущности. Получает доступ ко всему движку, поэтому может
+/// менять мир, создавать и удалять сущности (в том числе свою).
+/// В снимки мира попадают только скрипты, зарегистрированные через
+/// `Engine::register_persistent_script`
+pub trait Script: Any {
+    /// Вызывается один раз перед первым обновлением
+    fn start(&mut self, _entity: Entity, _engine: &mut Engine) {}
+
+    /// Вызывается каждый кадр с игровым временем кадра
+    fn update(&mut self, _entity: Entity, _engine: &mut Engine, _dt: f32) {}
+
+    /// Вызывается с фиксированным шагом, для детерминированной симуляции
+    fn fixed_update(&mut self, _entity: Entity, _engine: &mut Engine, _dt: f32) {}
+
+    /// Вызывается при удалении сущности или выгрузке сцены
+    fn on_destroy(&mut self, _entity: Entity, _engine: &mut Engine) {}
+}
+
+/// Скрипт, привязанный к сущности
+pub struct ScriptSlot {
+    pub script: Box<dyn Script>,
+    pub started: bool
+}
+
+impl ScriptSlot {
+    pub fn new(script: Box<dyn Script>) -> Self {
+        Self { script, started: false }
+    }
+}
@@ -1,19 +1,41 @@
-@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(0) var<uniform> uniforms: FrameUniforms;
 @group(0) @binding(1) var<storage, read> lights: array<Light>;
 @group(0) @binding(2) var<storage, read> light_count: LightCount;
-@group(0) @binding(3) var depth_texture: texture_depth_cube;
+@group(0) @binding(3) var shadow_maps: texture_depth_2d_array;
 @group(0) @binding(4) var depth_sampler: sampler_comparison;
+@group(0) @binding(5) var<storage, read> shadow_matrices: array<mat4x4<f32>>;
 
-struct Uniforms {
-    model: mat4x4<f32>,
+@group(1) @binding(0) var<uniform> object: ObjectUniforms;
+
+@group(2) @binding(0) var<uniform> material: Material;
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+@group(2) @binding(3) var normal_texture: texture_2d<f32>;
+@group(2) @binding(4) var normal_sampler: sampler;
+
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+const PI: f32 = 3.14159265;
+
+struct FrameUniforms {
     view: mat4x4<f32>,
     projection: mat4x4<f32>,
-    normal: mat4x4<f32>,
     camera_pos: vec3<f32>,
-    _padding1: f32,
-    light_pos: vec3<f32>,
-    light_far_plane: f32,
-    light_view_projection: mat4x4<f32>
+    _padding1: f32
+};
+
+struct ObjectUniforms {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>
+};
+
+struct Material {
+    base_color: vec3<f32>,
+    roughness: f32,
+    emissive: vec3<f32>,
+    metallic: f32
 };
 
 struct Light {
@@ -21,8 +43,12 @@ struct Light {
     light_type: u32,
     color: vec3<f32>,
     intensity: f32,
+    direction: vec3<f32>,
     range: f32,
-    _pad: array<f32, 6>
+    shadow_index: i32,
+    inner_angle: f32,
+    outer_angle: f32,
+    casts_shadows: u32
 };
 
 struct LightCount {
@@ -31,66 +57,192 @@ struct LightCount {
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) normal: vec3<f32>
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) tangent: vec4<f32>
 };
 
 struct VertexOutput {
     @builtin(position) clip_pos: vec4<f32>,
     @location(0) world_pos: vec3<f32>,
     @location(1) normal: vec3<f32>,
-    @location(2) light_to_frag_vec: vec3<f32>
+    @location(2) uv: vec2<f32>,
+    @location(3) tangent: vec4<f32>
 };
 
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
-    let model_pos = uniforms.model * vec4(input.position, 1.0);
+    let model_pos = object.model * vec4(input.position, 1.0);
     out.world_pos = model_pos.xyz;
-    out.normal = normalize((uniforms.normal * vec4(input.normal, 0.0)).xyz);
+    out.normal = normalize((object.normal * vec4(input.normal, 0.0)).xyz);
 
     out.clip_pos = uniforms.projection * uniforms.view * model_pos;
-    out.light_to_frag_vec = out.world_pos - uniforms.light_pos;
+    out.uv = input.uv;
+    out.tangent = vec4((object.model * vec4(input.tangent.xyz, 0.0)).xyz, input.tangent.w);
 
     return out;
 }
 
+// Тень из слоя карты теней: 1 - освещено, 0 - в тени
+fn layer_shadow(layer: i32, world_pos: vec3<f32>, normal: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+    let clip = shadow_matrices[layer] * vec4(world_pos, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let bias = max(0.002 * (1.0 - dot(normal, light_dir)), 0.0005);
+
+    return textureSampleCompareLevel(shadow_maps, depth_sampler, uv, layer, ndc.z - bias);
+}
+
+// Грань куба точечного света по направлению от источника.
+// Порядок +X, -X, +Y, -Y, +Z, -Z совпадает с shadows.rs
+fn cube_face(direction: vec3<f32>) -> i32 {
+    let a = abs(direction);
+
+    if (a.x >= a.y && a.x >= a.z) {
+        return select(1, 0, direction.x > 0.0);
+    }
+    if (a.y >= a.z) {
+        return select(3, 2, direction.y > 0.0);
+    }
+    return select(5, 4, direction.z > 0.0);
+}
+
+// Распределение микрограней GGX
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+
+    return a2 / (PI * d * d);
+}
+
+// Самозатенение микрограней по Смиту с аппроксимацией Шлика
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+    return g_v * g_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Отражённый свет от одного источника по модели Кука-Торренса.
+// radiance - пришедший свет с учётом затухания и тени
+fn shade(base_color: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>, light_dir: vec3<f32>, radiance: vec3<f32>) -> vec3<f32> {
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    if (n_dot_l <= 0.0) {
+        return vec3(0.0);
+    }
+
+    let roughness = clamp(material.roughness, 0.04, 1.0);
+    let half_dir = normalize(view_dir + light_dir);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+
+    let f0 = mix(vec3(0.04), base_color, material.metallic);
+    let fresnel = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+    let specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * fresnel
+        / (4.0 * n_dot_v * n_dot_l + 0.0001);
+
+    let diffuse = (vec3(1.0) - fresnel) * (1.0 - material.metallic) * base_color / PI;
+
+    return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// Нормаль из карты нормалей в соглашении OpenGL: зелёный канал вверх по
+// изображению, то есть против направления v. Без касательной остаётся нормаль вершины
+fn surface_normal(input: VertexOutput) -> vec3<f32> {
+    let normal = normalize(input.normal);
+    let mapped = textureSample(normal_texture, normal_sampler, input.uv).xyz * 2.0 - 1.0;
+
+    let tangent = input.tangent.xyz - normal * dot(normal, input.tangent.xyz);
+    if (dot(tangent, tangent) < 1e-8) {
+        return normal;
+    }
+
+    let t = normalize(tangent);
+    let b = cross(normal, t) * input.tangent.w;
+
+    return normalize(t * mapped.x - b * mapped.y + normal * mapped.z);
+}
+
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    let ambient = vec3(0.1);
+    let normal = surface_normal(input);
+    let view_dir = normalize(uniforms.camera_pos - input.world_pos);
+    let base_color = material.base_color * textureSample(base_color_texture, base_color_sampler, input.uv).rgb;
 
-    var lighting = ambient;
+    // Рассеянный свет окружения без отражений
+    let ambient = vec3(0.1) * base_color * (1.0 - material.metallic * 0.9);
+
+    var lighting = ambient + material.emissive;
 
     for (var i: u32 = 0u; i < light_count.count; i++) {
         let light = lights[i];
-        if (light.light_type != 0u) {
+
+        if (light.light_type == LIGHT_DIRECTIONAL) {
+            let light_dir = normalize(-light.direction);
+
+            var shadow = 1.0;
+            if (light.shadow_index >= 0) {
+                shadow = layer_shadow(light.shadow_index, input.world_pos, normal, light_dir);
+            }
+
+            lighting += shade(base_color, normal, view_dir, light_dir, light.color * light.intensity * shadow);
             continue;
         }
 
-        let light_dir = normalize(light.position - input.world_pos);
-        let dist_to_light = length(light.position - input.world_pos);
+        let to_light = light.position - input.world_pos;
+        let dist_to_light = length(to_light);
         if (dist_to_light > light.range) {
             continue;
         }
 
-        let diffuse_strength = max(dot(input.normal, light_dir), 0.0);
+        let light_dir = to_light / dist_to_light;
         let attenuation = 1.0 / (dist_to_light * dist_to_light + 0.001);
-        let diffuse = light.color * diffuse_strength * light.intensity * attenuation;
 
-        let frag_to_light = input.world_pos - uniforms.light_pos;
-        let current_depth = length(frag_to_light);
-        let direction = normalize(frag_to_light);
+        if (light.light_type == LIGHT_SPOT) {
+            let cos_angle = dot(-light_dir, normalize(light.direction));
+            let cos_inner = cos(radians(light.inner_angle));
+            let cos_outer = cos(radians(light.outer_angle));
+            // При равных углах края smoothstep не определён
+            let cone = smoothstep(cos_outer, max(cos_inner, cos_outer + 0.0001), cos_angle);
+            if (cone <= 0.0) {
+                continue;
+            }
+
+            var shadow = 1.0;
+            if (light.shadow_index >= 0) {
+                shadow = layer_shadow(light.shadow_index, input.world_pos, normal, light_dir);
+            }
+
+            lighting += shade(base_color, normal, view_dir, light_dir, light.color * light.intensity * attenuation * cone * shadow);
+            continue;
+        }
 
-        let bias = max(0.05 * (1.0 - dot(input.normal, -light_dir)), 0.01);
+        if (light.light_type != LIGHT_POINT) {
+            continue;
+        }
 
-        let shadow = textureSampleCompare(
-            depth_texture,
-            depth_sampler,
-            direction,
-            (current_depth - bias) / uniforms.light_far_plane
-        );
+        var shadow = 1.0;
+        if (light.shadow_index >= 0) {
+            let face = cube_face(input.world_pos - light.position);
+            shadow = layer_shadow(light.shadow_index + face, input.world_pos, normal, light_dir);
+        }
 
-        lighting += diffuse * shadow;
+        lighting += shade(base_color, normal, view_dir, light_dir, light.color * light.intensity * attenuation * shadow);
     }
 
     return vec4(lighting, 1.0);
@@ -1,9 +1,14 @@
-struct Uniforms {
-    model: mat4x4<f32>,
+struct ShadowUniforms {
     light_view_proj: mat4x4<f32>
 };
 
-@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+struct ObjectUniforms {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>
+};
+
+@group(0) @binding(0) var<uniform> uniforms: ShadowUniforms;
+@group(1) @binding(0) var<uniform> object: ObjectUniforms;
 
 struct VertexInput {
     @location(0) position: vec3<f32>
@@ -11,7 +16,7 @@ struct VertexInput {
 
 @vertex
 fn vs_main(input: VertexInput) -> @builtin(position) vec4<f32> {
-    let world_pos = uniforms.model * vec4(input.position, 1.0);
+    let world_pos = object.model * vec4(input.position, 1.0);
     let light_clip_pos = uniforms.light_view_proj * world_pos;
     return light_clip_pos;
 }
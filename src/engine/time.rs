@@ -0,0 +1,178 @@
+use std::time::Instant;
+
+/// Время кадра и шаг фиксированного обновления
+pub struct Time {
+    /// Множитель игрового времени
+    pub scale: f32,
+    /// Пауза останавливает игровое время и фиксированные шаги
+    pub paused: bool,
+    /// Длительность фиксированного шага, с
+    pub fixed_delta: f32,
+    /// Ограничение длительности кадра, чтобы после зависания не навёрстывать сотни шагов
+    pub max_delta: f32,
+    delta: f32,
+    real_delta: f32,
+    elapsed: f64,
+    real_elapsed: f64,
+    fixed_elapsed: f64,
+    accumulator: f32,
+    frame: u64,
+    last: Option<Instant>
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+            fixed_delta: 1.0 / 60.0,
+            max_delta: 0.25,
+            delta: 0.0,
+            real_delta: 0.0,
+            elapsed: 0.0,
+            real_elapsed: 0.0,
+            fixed_elapsed: 0.0,
+            accumulator: 0.0,
+            frame: 0,
+            last: None
+        }
+    }
+}
+
+impl Time {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Начало кадра по системным часам
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let real_delta = match self.last {
+            Some(last) => (now - last).as_secs_f32(),
+            None => 0.0
+        };
+
+        self.last = Some(now);
+        self.advance(real_delta);
+    }
+
+    /// Начало кадра с заданной длительностью
+    pub fn advance(&mut self, real_delta: f32) {
+        self.real_delta = real_delta.min(self.max_delta);
+        self.real_elapsed += self.real_delta as f64;
+        self.frame += 1;
+
+        self.delta = if self.paused { 0.0 } else { self.real_delta * self.scale };
+        self.elapsed += self.delta as f64;
+        self.accumulator += self.delta;
+    }
+
+    /// Списание накопленного времени на один фиксированный шаг
+    pub fn expend_fixed_step(&mut self) -> bool {
+        if self.accumulator >= self.fixed_delta {
+            self.accumulator -= self.fixed_delta;
+            self.fixed_elapsed += self.fixed_delta as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Игровое время кадра с учётом паузы и масштаба
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Реальное время кадра
+    pub fn real_delta(&self) -> f32 {
+        self.real_delta
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn real_elapsed(&self) -> f64 {
+        self.real_elapsed
+    }
+
+    pub fn fixed_elapsed(&self) -> f64 {
+        self.fixed_elapsed
+    }
+
+    /// Доля следующего фиксированного шага, для интерполяции при отрисовке
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.fixed_delta
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Шаги, кратные степеням двойки, считаются без погрешности
+    fn time(fixed_delta: f32, max_delta: f32) -> Time {
+        Time { fixed_delta, max_delta, ..Time::default() }
+    }
+
+    fn fixed_steps(time: &mut Time) -> u32 {
+        let mut steps = 0;
+        while time.expend_fixed_step() {
+            steps += 1;
+        }
+
+        steps
+    }
+
+    #[test]
+    fn accumulator_carries_remainder_between_frames() {
+        let mut time = time(0.25, 1.0);
+
+        time.advance(0.625);
+        assert_eq!(fixed_steps(&mut time), 2);
+        assert_eq!(time.alpha(), 0.5);
+
+        time.advance(0.125);
+        assert_eq!(fixed_steps(&mut time), 1);
+        assert_eq!(time.alpha(), 0.0);
+        assert_eq!(time.fixed_elapsed(), 0.75);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_to_max_delta() {
+        let mut time = time(0.125, 0.25);
+
+        time.advance(10.0);
+
+        assert_eq!(time.real_delta(), 0.25);
+        assert_eq!(time.delta(), 0.25);
+        assert_eq!(fixed_steps(&mut time), 2);
+    }
+
+    #[test]
+    fn pause_and_scale_affect_only_game_time() {
+        let mut time = time(0.25, 1.0);
+
+        time.scale = 2.0;
+        time.advance(0.25);
+        assert_eq!(time.delta(), 0.5);
+        assert_eq!(fixed_steps(&mut time), 2);
+
+        time.toggle_pause();
+        time.advance(0.25);
+        assert_eq!(time.delta(), 0.0);
+        assert_eq!(time.real_delta(), 0.25);
+        assert_eq!(fixed_steps(&mut time), 0);
+        assert_eq!(time.elapsed(), 0.5);
+        assert_eq!(time.real_elapsed(), 0.5);
+        assert_eq!(time.frame(), 2);
+    }
+}
@@ -1,7 +1,8 @@
-mod engine;
 mod app;
 mod scenes;
 
+use neothauma::engine;
+
 use winit::event_loop::*;
 use app::App;
 
@@ -1,5 +1,6 @@
 use crate::engine::engine::*;
 use crate::engine::objects::*;
+use crate::engine::prefab::*;
 use crate::engine::core::primitives::*;
 use crate::engine::render::transform::*;
 
@@ -7,6 +8,10 @@ pub fn load(engine: &mut Engine) {
     let light = light(engine);
     engine.edit_light(&light, Vec3::IDENTITY, 10.0, 1000.0);
 
+    // Солнце: сверху и сбоку, чтобы фигуры отбрасывали тени на пол
+    let rotation = Quat::from_axis_angle(Vec3::Y, 30f32.to_radians()) * Quat::from_axis_angle(Vec3::X, -60f32.to_radians());
+    engine.spawn_with(&directional_light_prefab(), Overrides::new().rotated(rotation).colored(Vec3::new(1.0, 0.95, 0.85)));
+
     let floor = cube(engine);
     engine.transform(&floor, Transform::new(Vec3::Y * -1.0, Quat::IDENTITY, Vec3::new(100.0, 0.1, 100.0)));
 
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use crate::engine::core::document::*;
+use crate::engine::engine::*;
+use crate::engine::objects::*;
+use crate::engine::prefab::*;
+use crate::engine::core::primitives::*;
+use crate::engine::render::material::*;
+use crate::engine::render::transform::*;
+use crate::engine::scene::manager::*;
+use crate::engine::scene::snapshot::*;
+use crate::engine::script::*;
+use crate::engine::ecs::*;
+use crate::engine::controllers::fly::*;
+use crate::engine::controllers::follow::*;
+
+/// Ряд шаров разного размера и цвета
+/// Камера следит за средним шаром
+#[derive(Default)]
+pub struct Spheres {
+    focus: Option<Entity>
+}
+
+impl Scene for Spheres {
+    fn load(&mut self, engine: &mut Engine) {
+        let light = light(engine);
+        engine.edit_light(&light, Vec3::new(1.0, 0.9, 0.8), 10.0, 1000.0);
+
+        let floor = cube(engine);
+        engine.transform(&floor, Transform::new(Vec3::Y * -1.0, Quat::IDENTITY, Vec3::new(100.0, 0.1, 100.0)));
+
+        let prefab = sphere_prefab(32).with_material(Material::default().with_roughness(0.3));
+        let colors = [
+            Vec3::new(0.9, 0.2, 0.2),
+            Vec3::new(0.9, 0.6, 0.1),
+            Vec3::new(0.2, 0.8, 0.3),
+            Vec3::new(0.2, 0.4, 0.9),
+            Vec3::new(0.7, 0.3, 0.9)
+        ];
+
+        for (i, color) in colors.into_iter().enumerate() {
+            let size = 0.3 + i as f32 * 0.2;
+            let overrides = Overrides::new()
+                .at(Vec3::new(i as f32 * 2.0 - 4.0, size - 0.9, -5.0))
+                .scaled(Vec3::IDENTITY * size)
+                .colored(color);
+            let sphere = engine.spawn_with(&prefab, overrides);
+            engine.add_script(sphere, Bob { phase: i as f32 * 0.5, base: 0.0 });
+
+            if i == 2 {
+                self.focus = Some(sphere);
+            }
+        }
+    }
+
+    fn on_enter(&mut self, engine: &mut Engine) {
+        engine.get_camera_mut().position = Vec3::new(0.0, 1.0, 2.0);
+
+        if let Some(focus) = self.focus {
+            engine.set_camera_controller(FollowCameraController::new(focus).with_offset(Vec3::new(0.0, 1.5, 4.0)));
+        }
+    }
+
+    fn on_restore(&mut self, _engine: &mut Engine, entities: &HashMap<Entity, Entity>) {
+        self.focus = self.focus.and_then(|focus| entities.get(&focus).copied());
+    }
+
+    fn on_exit(&mut self, engine: &mut Engine) {
+        engine.set_camera_controller(FlyCameraController::new());
+    }
+}
+
+/// Покачивание вверх-вниз
+pub struct Bob {
+    phase: f32,
+    base: f32
+}
+
+impl Script for Bob {
+    fn start(&mut self, entity: Entity, engine: &mut Engine) {
+        if let Some(transform) = engine.ecs.get_transform(&entity) {
+            self.base = transform.position.y;
+        }
+    }
+
+    fn fixed_update(&mut self, entity: Entity, engine: &mut Engine, dt: f32) {
+        self.phase += dt * 2.0;
+
+        if let Some(transform) = engine.ecs.get_transform_mut(&entity) {
+            transform.position.y = self.base + self.phase.sin() * 0.3;
+        }
+    }
+}
+
+impl Persistent for Bob {
+    const NAME: &'static str = "bob";
+
+    fn save(&self, section: &mut Section) {
+        section.set("phase", self.phase);
+        section.set("base", self.base);
+    }
+
+    fn load(section: &Section) -> Result<Self, String> {
+        Ok(Self {
+            phase: section.get_f32("phase")?.unwrap_or_default(),
+            base: section.get_f32("base")?.unwrap_or_default()
+        })
+    }
+}
@@ -1 +1,15 @@
+use crate::engine::engine::*;
+use crate::engine::scene::file::*;
+
 pub mod _1;
+pub mod _2;
+
+/// Сцена, загружаемая при старте
+pub const START_SCENE: &str = "_1";
+
+pub fn register(engine: &mut Engine) {
+    engine.register_scene("_1", _1::load);
+    engine.register_scene("_2", _2::Spheres::default());
+    engine.register_persistent_script::<_2::Bob>();
+    engine.register_scene("demo", FileScene::new("assets/scenes/demo.scene"));
+}